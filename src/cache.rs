@@ -0,0 +1,309 @@
+use std::collections::{BTreeMap, HashMap};
+
+use crate::{Aabb, ChunkData, Coord};
+
+#[derive(PartialEq, Eq, Clone, Copy, Default, Debug)]
+pub struct CacheStats {
+    pub hits: u64,
+    pub misses: u64,
+    pub evictions: u64,
+}
+
+struct Entry<const X: usize, const Y: usize> {
+    chunk: ChunkData<X, Y>,
+    used: u64,
+}
+
+type EvictCallback<const X: usize, const Y: usize> = Box<dyn FnMut(Coord, ChunkData<X, Y>)>;
+
+/// Holds up to `capacity` chunks by chunk coordinate, evicting the least recently used one
+/// whenever an insert goes over.
+///
+/// Evicted chunks are passed to the eviction callback if one is set, and returned from the
+/// call that evicted them otherwise. Chunks in the area set by `retain_area` and the most
+/// recently used chunk are never evicted, so the cache can grow past its capacity while pinned
+/// chunks fill it.
+pub struct ChunkCache<const X: usize, const Y: usize> {
+    capacity: usize,
+    entries: HashMap<Coord, Entry<X, Y>>,
+    order: BTreeMap<u64, Coord>,
+    tick: u64,
+    pinned: Option<Aabb>,
+    on_evict: Option<EvictCallback<X, Y>>,
+    stats: CacheStats,
+}
+
+impl<const X: usize, const Y: usize> ChunkCache<X, Y> {
+    /// # Panics
+    ///
+    /// Panics if `capacity` is zero.
+    pub fn new(capacity: usize) -> Self {
+        assert!(capacity > 0, "chunk cache capacity must be non-zero");
+        Self {
+            capacity,
+            entries: HashMap::new(),
+            order: BTreeMap::new(),
+            tick: 0,
+            pinned: None,
+            on_evict: None,
+            stats: CacheStats::default(),
+        }
+    }
+
+    /// Sets the callback that receives evicted chunks, e.g. to write them to a `RegionStore`.
+    pub fn with_eviction_callback(
+        mut self,
+        on_evict: impl FnMut(Coord, ChunkData<X, Y>) + 'static,
+    ) -> Self {
+        self.on_evict = Some(Box::new(on_evict));
+        self
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn contains(&self, coord: Coord) -> bool {
+        self.entries.contains_key(&coord)
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        self.stats
+    }
+
+    pub fn reset_stats(&mut self) {
+        self.stats = CacheStats::default();
+    }
+
+    fn touch(&mut self, coord: Coord) -> Option<&mut Entry<X, Y>> {
+        let entry = self.entries.get_mut(&coord)?;
+        self.order.remove(&entry.used);
+        self.tick += 1;
+        entry.used = self.tick;
+        self.order.insert(self.tick, coord);
+        Some(entry)
+    }
+
+    /// Looks up a chunk, counting a hit or miss and marking it as recently used.
+    pub fn get(&mut self, coord: Coord) -> Option<&ChunkData<X, Y>> {
+        self.get_mut(coord).map(|chunk| &*chunk)
+    }
+
+    pub fn get_mut(&mut self, coord: Coord) -> Option<&mut ChunkData<X, Y>> {
+        if self.contains(coord) {
+            self.stats.hits += 1;
+        } else {
+            self.stats.misses += 1;
+        }
+        self.touch(coord).map(|entry| &mut entry.chunk)
+    }
+
+    /// Looks up a chunk without affecting the statistics or the eviction order.
+    pub fn peek(&self, coord: Coord) -> Option<&ChunkData<X, Y>> {
+        self.entries.get(&coord).map(|entry| &entry.chunk)
+    }
+
+    /// Inserts or replaces the chunk at `coord` as the most recently used.
+    pub fn insert(
+        &mut self,
+        coord: Coord,
+        chunk: ChunkData<X, Y>,
+    ) -> Vec<(Coord, ChunkData<X, Y>)> {
+        if let Some(entry) = self.touch(coord) {
+            entry.chunk = chunk;
+            return Vec::new();
+        }
+        self.tick += 1;
+        self.entries.insert(
+            coord,
+            Entry {
+                chunk,
+                used: self.tick,
+            },
+        );
+        self.order.insert(self.tick, coord);
+        self.evict()
+    }
+
+    /// Returns the cached chunk, or loads it with `load` on a miss. Evictions caused by the
+    /// load are passed to the callback, or dropped if none is set.
+    pub fn get_or_insert_with(
+        &mut self,
+        coord: Coord,
+        load: impl FnOnce() -> ChunkData<X, Y>,
+    ) -> &mut ChunkData<X, Y> {
+        if self.get_mut(coord).is_none() {
+            self.insert(coord, load());
+        }
+        match self.entries.get_mut(&coord) {
+            Some(entry) => &mut entry.chunk,
+            None => unreachable!("the most recently used chunk is never evicted"),
+        }
+    }
+
+    pub fn remove(&mut self, coord: Coord) -> Option<ChunkData<X, Y>> {
+        let entry = self.entries.remove(&coord)?;
+        self.order.remove(&entry.used);
+        Some(entry.chunk)
+    }
+
+    /// Pins every chunk in `area`, replacing any earlier pinned area, and evicts down to the
+    /// capacity from the chunks outside it.
+    pub fn retain_area(&mut self, area: Aabb) -> Vec<(Coord, ChunkData<X, Y>)> {
+        self.pinned = Some(area);
+        self.evict()
+    }
+
+    pub fn unpin(&mut self) -> Vec<(Coord, ChunkData<X, Y>)> {
+        self.pinned = None;
+        self.evict()
+    }
+
+    /// Removes every chunk, least recently used first, e.g. to save them all on shutdown.
+    pub fn drain(&mut self) -> Vec<(Coord, ChunkData<X, Y>)> {
+        let order = std::mem::take(&mut self.order);
+        order
+            .into_values()
+            .filter_map(|coord| Some((coord, self.entries.remove(&coord)?.chunk)))
+            .collect()
+    }
+
+    fn evict(&mut self) -> Vec<(Coord, ChunkData<X, Y>)> {
+        let mut evicted = Vec::new();
+        while self.entries.len() > self.capacity {
+            let (pinned, newest) = (self.pinned, self.tick);
+            let victim = self
+                .order
+                .iter()
+                .find(|(&used, &coord)| {
+                    used != newest && !pinned.is_some_and(|area| area.contains(coord))
+                })
+                .map(|(&used, &coord)| (used, coord));
+            let (used, coord) = match victim {
+                Some(victim) => victim,
+                None => break,
+            };
+            self.order.remove(&used);
+            let chunk = self.entries.remove(&coord).unwrap().chunk;
+            self.stats.evictions += 1;
+            match &mut self.on_evict {
+                Some(on_evict) => on_evict(coord, chunk),
+                None => evicted.push((coord, chunk)),
+            }
+        }
+        evicted
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::RefCell, rc::Rc};
+
+    use super::*;
+
+    fn chunk(block: usize) -> ChunkData<2, 2> {
+        ChunkData::new(block)
+    }
+
+    fn coords(evicted: &[(Coord, ChunkData<2, 2>)]) -> Vec<Coord> {
+        evicted.iter().map(|&(coord, _)| coord).collect()
+    }
+
+    #[test]
+    fn evicts_the_least_recently_used_chunk() {
+        let mut cache = ChunkCache::<2, 2>::new(2);
+        assert!(cache.insert(Coord(0, 0), chunk(0)).is_empty());
+        assert!(cache.insert(Coord(1, 0), chunk(1)).is_empty());
+        cache.get(Coord(0, 0));
+        let evicted = cache.insert(Coord(2, 0), chunk(2));
+        assert_eq!(evicted, [(Coord(1, 0), chunk(1))]);
+        assert_eq!(cache.len(), 2);
+        // Peeking doesn't refresh (0, 0), but replacing it does.
+        cache.peek(Coord(0, 0));
+        assert!(cache.insert(Coord(0, 0), chunk(5)).is_empty());
+        assert_eq!(coords(&cache.insert(Coord(3, 0), chunk(3))), [Coord(2, 0)]);
+        assert_eq!(cache.peek(Coord(0, 0)), Some(&chunk(5)));
+    }
+
+    #[test]
+    fn eviction_callback_receives_evicted_chunks() {
+        let seen = Rc::new(RefCell::new(Vec::new()));
+        let sink = Rc::clone(&seen);
+        let mut cache = ChunkCache::<2, 2>::new(1)
+            .with_eviction_callback(move |coord, chunk| sink.borrow_mut().push((coord, chunk)));
+        cache.insert(Coord(0, 0), chunk(0));
+        assert!(cache.insert(Coord(1, 0), chunk(1)).is_empty());
+        cache.get_or_insert_with(Coord(2, 0), || chunk(2));
+        assert_eq!(
+            *seen.borrow(),
+            [(Coord(0, 0), chunk(0)), (Coord(1, 0), chunk(1))]
+        );
+        assert_eq!(cache.stats().evictions, 2);
+    }
+
+    #[test]
+    fn pinned_chunks_survive_eviction() {
+        let mut cache = ChunkCache::<2, 2>::new(2);
+        assert!(cache
+            .retain_area(Aabb::new(Coord(0, 0), Coord(1, 1)))
+            .is_empty());
+        cache.insert(Coord(0, 0), chunk(0));
+        cache.insert(Coord(1, 0), chunk(1));
+        assert!(cache.insert(Coord(5, 5), chunk(5)).is_empty());
+        assert_eq!(coords(&cache.insert(Coord(6, 6), chunk(6))), [Coord(5, 5)]);
+        // Both pinned chunks stay; the newest chunk is kept even though that overfills the cache.
+        assert!(cache.contains(Coord(0, 0)) && cache.contains(Coord(1, 0)));
+        assert!(cache.contains(Coord(6, 6)));
+        assert_eq!(cache.len(), 3);
+        assert_eq!(coords(&cache.unpin()), [Coord(0, 0)]);
+        assert_eq!(cache.len(), 2);
+    }
+
+    #[test]
+    fn stats_count_hits_misses_and_evictions() {
+        let mut cache = ChunkCache::<2, 2>::new(1);
+        let mut loads = 0;
+        for _ in 0..2 {
+            cache.get_or_insert_with(Coord(0, 0), || {
+                loads += 1;
+                chunk(0)
+            });
+        }
+        assert_eq!(loads, 1);
+        assert!(cache.get(Coord(1, 0)).is_none());
+        cache.insert(Coord(1, 0), chunk(1));
+        cache.peek(Coord(0, 0));
+        assert_eq!(
+            cache.stats(),
+            CacheStats {
+                hits: 1,
+                misses: 2,
+                evictions: 1
+            }
+        );
+        cache.reset_stats();
+        assert_eq!(cache.stats(), CacheStats::default());
+    }
+
+    #[test]
+    fn drain_returns_least_recently_used_first() {
+        let mut cache = ChunkCache::<2, 2>::new(3);
+        for x in 0..3 {
+            cache.insert(Coord(x, 0), chunk(x as usize));
+        }
+        cache.get_mut(Coord(0, 0)).unwrap()[0][0] = 9;
+        let drained = cache.drain();
+        assert_eq!(coords(&drained), [Coord(1, 0), Coord(2, 0), Coord(0, 0)]);
+        assert_eq!(drained[2].1[0][0], 9);
+        assert!(cache.is_empty());
+        assert!(cache.drain().is_empty());
+    }
+}
@@ -0,0 +1,156 @@
+use std::{
+    convert::TryFrom,
+    ops::{Index, IndexMut},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{BlockID, ChunkData, DeSerializable, LengthError, OutOfBoundsError};
+
+/// A stack of `Z` two-dimensional layers of cells of type `T`, indexed as `chunk[z][y][x]`.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+#[serde(bound = "T: Serialize + serde::de::DeserializeOwned + Default + Copy")]
+pub struct ChunkData3D<const X: usize, const Y: usize, const Z: usize, T = BlockID>(
+    #[serde(with = "DeSerializable")]
+    [ChunkData<X, Y, T>; Z],
+);
+
+impl<const X: usize, const Y: usize, const Z: usize, T: Copy> ChunkData3D<X, Y, Z, T> {
+    pub fn new(value: T) -> Self {
+        Self([ChunkData::new(value); Z])
+    }
+
+    pub fn from_layers(layers: [ChunkData<X, Y, T>; Z]) -> Self {
+        Self(layers)
+    }
+
+    pub fn into_layers(self) -> [ChunkData<X, Y, T>; Z] {
+        self.0
+    }
+
+    pub fn layers(&self) -> &[ChunkData<X, Y, T>; Z] {
+        &self.0
+    }
+
+    pub fn layers_mut(&mut self) -> &mut [ChunkData<X, Y, T>; Z] {
+        &mut self.0
+    }
+
+    pub fn fill(&mut self, value: T) {
+        for layer in &mut self.0 {
+            layer.fill(value);
+        }
+    }
+
+    pub fn get(&self, x: usize, y: usize, z: usize) -> Option<&T> {
+        self.0.get(z)?.get(x, y)
+    }
+
+    pub fn get_mut(&mut self, x: usize, y: usize, z: usize) -> Option<&mut T> {
+        self.0.get_mut(z)?.get_mut(x, y)
+    }
+
+    pub fn set(&mut self, x: usize, y: usize, z: usize, value: T) -> Result<(), OutOfBoundsError> {
+        *self.get_mut(x, y, z).ok_or(OutOfBoundsError)? = value;
+        Ok(())
+    }
+}
+
+impl<const X: usize, const Y: usize, const Z: usize, T: Copy + Default> Default
+    for ChunkData3D<X, Y, Z, T>
+{
+    fn default() -> Self {
+        Self([ChunkData::default(); Z])
+    }
+}
+
+impl<const X: usize, const Y: usize, const Z: usize, T> Index<usize> for ChunkData3D<X, Y, Z, T> {
+    type Output = ChunkData<X, Y, T>;
+    fn index(&self, index: usize) -> &Self::Output {
+        &self.0[index]
+    }
+}
+
+impl<const X: usize, const Y: usize, const Z: usize, T> IndexMut<usize>
+    for ChunkData3D<X, Y, Z, T>
+{
+    fn index_mut(&mut self, index: usize) -> &mut Self::Output {
+        &mut self.0[index]
+    }
+}
+
+impl<const X: usize, const Y: usize, const Z: usize, T> From<[ChunkData<X, Y, T>; Z]>
+    for ChunkData3D<X, Y, Z, T>
+{
+    fn from(layers: [ChunkData<X, Y, T>; Z]) -> Self {
+        Self(layers)
+    }
+}
+
+/// Builds a chunk from exactly `Z` layers.
+impl<const X: usize, const Y: usize, const Z: usize, T: Copy> TryFrom<&[ChunkData<X, Y, T>]>
+    for ChunkData3D<X, Y, Z, T>
+{
+    type Error = LengthError;
+    fn try_from(layers: &[ChunkData<X, Y, T>]) -> Result<Self, Self::Error> {
+        <[ChunkData<X, Y, T>; Z]>::try_from(layers)
+            .map(Self)
+            .map_err(|_| LengthError {
+                expected: Z,
+                actual: layers.len(),
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_value;
+
+    #[test]
+    fn layers_are_indexed_by_z() {
+        let mut chunk = ChunkData3D::<3, 2, 4>::new(1);
+        chunk[2][1][0] = 5;
+        assert_eq!(chunk.layers()[2][1][0], 5);
+        assert_eq!(chunk[1], ChunkData::new(1));
+        chunk.layers_mut()[3].fill(8);
+        let layers = chunk.into_layers();
+        assert_eq!(layers[3], ChunkData::new(8));
+        assert_eq!(ChunkData3D::from(layers), chunk);
+    }
+
+    #[test]
+    fn get_and_set_check_all_three_bounds() {
+        let mut chunk = ChunkData3D::<3, 2, 4>::default();
+        assert_eq!(chunk.set(2, 1, 3, 7), Ok(()));
+        assert_eq!(chunk.get(2, 1, 3), Some(&7));
+        assert_eq!(chunk[3][1][2], 7);
+        for (x, y, z) in [(3, 0, 0), (0, 2, 0), (0, 0, 4)] {
+            assert_eq!(chunk.get(x, y, z), None);
+            assert_eq!(chunk.set(x, y, z, 1), Err(OutOfBoundsError));
+        }
+    }
+
+    #[test]
+    fn try_from_requires_exactly_z_layers() {
+        let layers = [ChunkData::<2, 2>::new(1), ChunkData::new(2)];
+        let chunk = ChunkData3D::<2, 2, 2>::try_from(&layers[..]).unwrap();
+        assert_eq!(chunk[1], ChunkData::new(2));
+        assert_eq!(
+            ChunkData3D::<2, 2, 3>::try_from(&layers[..]),
+            Err(LengthError {
+                expected: 3,
+                actual: 2
+            })
+        );
+    }
+
+    #[test]
+    fn generic_cells_round_trip_through_serde() {
+        let mut chunk = ChunkData3D::<2, 1, 2, bool>::default();
+        chunk.set(1, 0, 1, true).unwrap();
+        let back: ChunkData3D<2, 1, 2, bool> =
+            test_value::from_value(test_value::to_value(&chunk).unwrap()).unwrap();
+        assert_eq!(back, chunk);
+    }
+}
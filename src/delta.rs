@@ -0,0 +1,199 @@
+use std::iter::FromIterator;
+
+use serde::{de::Error, Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::{BlockID, ChunkData, Coord, OutOfBoundsError};
+
+/// The cells that differ between two chunks, as local coordinates and their new blocks.
+///
+/// Changes are kept in row-major order with at most one per cell. The serialized form is just
+/// that list of `(coord, block)` pairs.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct ChunkDelta<const X: usize, const Y: usize> {
+    changes: Vec<(Coord, BlockID)>,
+}
+
+/// Sorts changes into row-major order, keeping only the last change to each cell.
+fn normalise(mut changes: Vec<(Coord, BlockID)>) -> Vec<(Coord, BlockID)> {
+    changes.sort_by_key(|&(coord, _)| (coord.1, coord.0));
+    let mut out: Vec<(Coord, BlockID)> = Vec::with_capacity(changes.len());
+    for (coord, block) in changes {
+        match out.last_mut() {
+            Some(last) if last.0 == coord => last.1 = block,
+            _ => out.push((coord, block)),
+        }
+    }
+    out
+}
+
+fn in_bounds<const X: usize, const Y: usize>(coord: Coord) -> bool {
+    coord.to_usize().is_some_and(|(x, y)| x < X && y < Y)
+}
+
+impl<const X: usize, const Y: usize> ChunkDelta<X, Y> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.changes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+
+    pub fn changes(&self) -> &[(Coord, BlockID)] {
+        &self.changes
+    }
+
+    /// Combines two deltas into one that has the effect of applying `self` and then `other`.
+    pub fn merge(&self, other: &Self) -> Self {
+        let mut changes = self.changes.clone();
+        changes.extend_from_slice(&other.changes);
+        Self {
+            changes: normalise(changes),
+        }
+    }
+}
+
+impl<const X: usize, const Y: usize> Default for ChunkDelta<X, Y> {
+    fn default() -> Self {
+        Self {
+            changes: Vec::new(),
+        }
+    }
+}
+
+/// Collects changes, ignoring any outside the chunk. Later changes to a cell win.
+impl<const X: usize, const Y: usize> FromIterator<(Coord, BlockID)> for ChunkDelta<X, Y> {
+    fn from_iter<I: IntoIterator<Item = (Coord, BlockID)>>(iter: I) -> Self {
+        Self {
+            changes: normalise(
+                iter.into_iter()
+                    .filter(|&(coord, _)| in_bounds::<X, Y>(coord))
+                    .collect(),
+            ),
+        }
+    }
+}
+
+impl<const X: usize, const Y: usize> ChunkData<X, Y> {
+    /// The delta that turns `self` into `other`.
+    pub fn diff(&self, other: &Self) -> ChunkDelta<X, Y> {
+        ChunkDelta {
+            changes: self
+                .iter()
+                .zip(other.iter())
+                .filter(|((_, old), (_, new))| old != new)
+                .map(|(_, (coord, &new))| (coord, new))
+                .collect(),
+        }
+    }
+
+    pub fn apply(&mut self, delta: &ChunkDelta<X, Y>) {
+        self.extend(delta.changes.iter().copied());
+    }
+}
+
+impl<const X: usize, const Y: usize> Serialize for ChunkDelta<X, Y> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.changes.serialize(serializer)
+    }
+}
+
+impl<'de, const X: usize, const Y: usize> Deserialize<'de> for ChunkDelta<X, Y> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let changes = Vec::<(Coord, BlockID)>::deserialize(deserializer)?;
+        if !changes.iter().all(|&(coord, _)| in_bounds::<X, Y>(coord)) {
+            return Err(D::Error::custom(OutOfBoundsError));
+        }
+        Ok(Self {
+            changes: normalise(changes),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_value::{self, Value};
+
+    fn numbered() -> ChunkData<3, 2> {
+        let mut chunk = ChunkData::default();
+        for (i, (_, cell)) in chunk.iter_mut().enumerate() {
+            *cell = i + 1;
+        }
+        chunk
+    }
+
+    #[test]
+    fn applying_a_diff_reproduces_the_target() {
+        let a = numbered();
+        let mut b = a;
+        b[0][2] = 9;
+        b[1][0] = 0;
+        let delta = a.diff(&b);
+        assert_eq!(delta.changes(), [(Coord(2, 0), 9), (Coord(0, 1), 0)]);
+        let mut patched = a;
+        patched.apply(&delta);
+        assert_eq!(patched, b);
+        assert!(a.diff(&a).is_empty());
+    }
+
+    #[test]
+    fn merge_keeps_the_later_change() {
+        let first: ChunkDelta<3, 2> = vec![(Coord(1, 1), 4), (Coord(0, 0), 5)]
+            .into_iter()
+            .collect();
+        let second: ChunkDelta<3, 2> = vec![(Coord(1, 1), 7), (Coord(2, 0), 8)]
+            .into_iter()
+            .collect();
+        assert_eq!(
+            first.merge(&second).changes(),
+            [(Coord(0, 0), 5), (Coord(2, 0), 8), (Coord(1, 1), 7)]
+        );
+        assert_eq!(second.merge(&first).changes()[2], (Coord(1, 1), 4));
+
+        let mut sequential = numbered();
+        sequential.apply(&first);
+        sequential.apply(&second);
+        let mut merged = numbered();
+        merged.apply(&first.merge(&second));
+        assert_eq!(merged, sequential);
+    }
+
+    #[test]
+    fn from_iter_drops_out_of_bounds_changes() {
+        let delta: ChunkDelta<3, 2> = vec![(Coord(3, 0), 1), (Coord(0, -1), 1), (Coord(2, 1), 1)]
+            .into_iter()
+            .collect();
+        assert_eq!(delta.changes(), [(Coord(2, 1), 1)]);
+    }
+
+    #[test]
+    fn serde_round_trip() {
+        let delta: ChunkDelta<3, 2> = vec![(Coord(1, 0), 3), (Coord(2, 1), 6)]
+            .into_iter()
+            .collect();
+        let back: ChunkDelta<3, 2> =
+            test_value::from_value(test_value::to_value(&delta).unwrap()).unwrap();
+        assert_eq!(back, delta);
+    }
+
+    #[test]
+    fn deserialize_rejects_out_of_bounds_coordinates() {
+        let change = |x: i64, y: i64| {
+            Value::Seq(vec![
+                Value::Seq(vec![Value::I64(x), Value::I64(y)]),
+                Value::U64(1),
+            ])
+        };
+        let ok = Value::Seq(vec![change(2, 1)]);
+        assert!(test_value::from_value::<ChunkDelta<3, 2>>(ok).is_ok());
+        for (x, y) in [(3, 0), (0, 2), (-1, 0)] {
+            let bad = Value::Seq(vec![change(0, 0), change(x, y)]);
+            assert!(test_value::from_value::<ChunkDelta<3, 2>>(bad).is_err());
+        }
+    }
+}
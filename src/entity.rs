@@ -0,0 +1,251 @@
+use std::{collections::BTreeMap, convert::TryFrom, error, fmt};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{Aabb, Coord};
+
+/// Identifies an entity for its whole lifetime, including across chunk transfers, so callers
+/// must keep ids unique across the world.
+#[derive(PartialEq, Eq, Hash, PartialOrd, Ord, Clone, Copy, Serialize, Deserialize, Debug)]
+pub struct EntityId(pub u64);
+
+impl fmt::Display for EntityId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "#{}", self.0)
+    }
+}
+
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum EntityError {
+    UnknownId(EntityId),
+    DuplicateId(EntityId),
+    OutOfBounds(Coord),
+}
+
+impl fmt::Display for EntityError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EntityError::UnknownId(id) => write!(f, "no entity {} in this chunk", id),
+            EntityError::DuplicateId(id) => write!(f, "entity {} is already in this chunk", id),
+            EntityError::OutOfBounds(position) => {
+                write!(f, "position {} is outside the chunk", position)
+            }
+        }
+    }
+}
+
+impl error::Error for EntityError {}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct EntityEntry<E> {
+    pub position: Coord,
+    pub entity: E,
+}
+
+#[derive(Deserialize)]
+struct RawEntityChunk<E> {
+    entities: BTreeMap<EntityId, EntityEntry<E>>,
+}
+
+/// The dynamic entities of one `X x Y` chunk, each at a local position inside it.
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+#[serde(try_from = "RawEntityChunk<E>")]
+pub struct EntityChunk<E, const X: usize, const Y: usize> {
+    entities: BTreeMap<EntityId, EntityEntry<E>>,
+}
+
+fn check_bounds<const X: usize, const Y: usize>(position: Coord) -> Result<(), EntityError> {
+    match position.to_usize() {
+        Some((x, y)) if x < X && y < Y => Ok(()),
+        _ => Err(EntityError::OutOfBounds(position)),
+    }
+}
+
+impl<E, const X: usize, const Y: usize> EntityChunk<E, X, Y> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entities.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entities.is_empty()
+    }
+
+    pub fn contains(&self, id: EntityId) -> bool {
+        self.entities.contains_key(&id)
+    }
+
+    pub fn insert(&mut self, id: EntityId, position: Coord, entity: E) -> Result<(), EntityError> {
+        check_bounds::<X, Y>(position)?;
+        if self.contains(id) {
+            return Err(EntityError::DuplicateId(id));
+        }
+        self.entities.insert(id, EntityEntry { position, entity });
+        Ok(())
+    }
+
+    pub fn remove(&mut self, id: EntityId) -> Option<EntityEntry<E>> {
+        self.entities.remove(&id)
+    }
+
+    pub fn get(&self, id: EntityId) -> Option<&EntityEntry<E>> {
+        self.entities.get(&id)
+    }
+
+    pub fn get_mut(&mut self, id: EntityId) -> Option<&mut E> {
+        self.entities.get_mut(&id).map(|entry| &mut entry.entity)
+    }
+
+    /// Moves an entity within the chunk. Use `transfer_to` once it leaves.
+    pub fn set_position(&mut self, id: EntityId, position: Coord) -> Result<(), EntityError> {
+        check_bounds::<X, Y>(position)?;
+        self.entities
+            .get_mut(&id)
+            .ok_or(EntityError::UnknownId(id))?
+            .position = position;
+        Ok(())
+    }
+
+    /// All entities in ascending id order.
+    pub fn iter(&self) -> impl Iterator<Item = (EntityId, &EntityEntry<E>)> {
+        self.entities.iter().map(|(&id, entry)| (id, entry))
+    }
+
+    /// Entities whose position lies in `area`, in ascending id order.
+    pub fn query(&self, area: Aabb) -> impl Iterator<Item = (EntityId, &EntityEntry<E>)> {
+        self.iter()
+            .filter(move |(_, entry)| area.contains(entry.position))
+    }
+
+    /// Moves `id` into `neighbor` at `position`, given in the neighbour's local coordinates
+    /// (`Coord::to_chunk_and_local` converts a world position). Nothing changes on error.
+    pub fn transfer_to(
+        &mut self,
+        id: EntityId,
+        neighbor: &mut Self,
+        position: Coord,
+    ) -> Result<(), EntityError> {
+        check_bounds::<X, Y>(position)?;
+        if !self.contains(id) {
+            return Err(EntityError::UnknownId(id));
+        }
+        if neighbor.contains(id) {
+            return Err(EntityError::DuplicateId(id));
+        }
+        let entry = self.entities.remove(&id).unwrap();
+        neighbor.entities.insert(
+            id,
+            EntityEntry {
+                position,
+                entity: entry.entity,
+            },
+        );
+        Ok(())
+    }
+}
+
+impl<E, const X: usize, const Y: usize> Default for EntityChunk<E, X, Y> {
+    fn default() -> Self {
+        Self {
+            entities: BTreeMap::new(),
+        }
+    }
+}
+
+impl<E, const X: usize, const Y: usize> TryFrom<RawEntityChunk<E>> for EntityChunk<E, X, Y> {
+    type Error = EntityError;
+    fn try_from(raw: RawEntityChunk<E>) -> Result<Self, Self::Error> {
+        for entry in raw.entities.values() {
+            check_bounds::<X, Y>(entry.position)?;
+        }
+        Ok(Self {
+            entities: raw.entities,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    type Chunk = EntityChunk<&'static str, 4, 4>;
+
+    fn ids<'a>(
+        entries: impl Iterator<Item = (EntityId, &'a EntityEntry<&'static str>)>,
+    ) -> Vec<u64> {
+        entries.map(|(id, _)| id.0).collect()
+    }
+
+    #[test]
+    fn insert_get_and_remove() {
+        let mut chunk = Chunk::new();
+        chunk.insert(EntityId(2), Coord(1, 3), "cow").unwrap();
+        chunk.insert(EntityId(1), Coord(0, 0), "pig").unwrap();
+        assert_eq!(chunk.len(), 2);
+        assert_eq!(chunk.get(EntityId(2)).unwrap().position, Coord(1, 3));
+        *chunk.get_mut(EntityId(1)).unwrap() = "boar";
+        assert_eq!(ids(chunk.iter()), [1, 2]);
+        assert_eq!(
+            chunk.insert(EntityId(2), Coord(0, 0), "sheep"),
+            Err(EntityError::DuplicateId(EntityId(2)))
+        );
+        assert_eq!(
+            chunk.remove(EntityId(1)),
+            Some(EntityEntry {
+                position: Coord(0, 0),
+                entity: "boar"
+            })
+        );
+        assert_eq!(chunk.remove(EntityId(1)), None);
+        assert!(!chunk.contains(EntityId(1)));
+    }
+
+    #[test]
+    fn query_by_local_position() {
+        let mut chunk = Chunk::new();
+        chunk.insert(EntityId(1), Coord(0, 0), "a").unwrap();
+        chunk.insert(EntityId(2), Coord(2, 2), "b").unwrap();
+        chunk.insert(EntityId(3), Coord(3, 1), "c").unwrap();
+        let area = Aabb::new(Coord(1, 1), Coord(3, 2));
+        assert_eq!(ids(chunk.query(area)), [2, 3]);
+        chunk.set_position(EntityId(1), Coord(1, 2)).unwrap();
+        assert_eq!(ids(chunk.query(area)), [1, 2, 3]);
+        assert_eq!(
+            chunk.set_position(EntityId(9), Coord(1, 1)),
+            Err(EntityError::UnknownId(EntityId(9)))
+        );
+    }
+
+    #[test]
+    fn out_of_bounds_positions_are_rejected() {
+        let mut chunk = Chunk::new();
+        for position in [Coord(4, 0), Coord(0, 4), Coord(-1, 2)] {
+            assert_eq!(
+                chunk.insert(EntityId(1), position, "x"),
+                Err(EntityError::OutOfBounds(position))
+            );
+        }
+        chunk.insert(EntityId(1), Coord(3, 3), "x").unwrap();
+        assert_eq!(
+            chunk.set_position(EntityId(1), Coord(3, 4)),
+            Err(EntityError::OutOfBounds(Coord(3, 4)))
+        );
+        assert_eq!(chunk.get(EntityId(1)).unwrap().position, Coord(3, 3));
+    }
+
+    #[test]
+    fn transfer_moves_the_entity() {
+        let (mut a, mut b) = (Chunk::new(), Chunk::new());
+        a.insert(EntityId(1), Coord(3, 0), "x").unwrap();
+        assert_eq!(
+            a.transfer_to(EntityId(1), &mut b, Coord(4, 0)),
+            Err(EntityError::OutOfBounds(Coord(4, 0)))
+        );
+        a.transfer_to(EntityId(1), &mut b, Coord(0, 0)).unwrap();
+        assert!(a.is_empty());
+        assert_eq!(b.get(EntityId(1)).unwrap().position, Coord(0, 0));
+    }
+}
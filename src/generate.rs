@@ -0,0 +1,232 @@
+use std::sync::Arc;
+
+use crate::{BlockID, ChunkData, Coord, World};
+
+/// Produces the contents of a chunk from its chunk coordinate.
+///
+/// Closures taking a `Coord` and returning a chunk implement this directly.
+pub trait ChunkGenerator<const X: usize, const Y: usize> {
+    fn generate(&self, chunk_coord: Coord) -> ChunkData<X, Y>;
+}
+
+impl<F: Fn(Coord) -> ChunkData<X, Y>, const X: usize, const Y: usize> ChunkGenerator<X, Y> for F {
+    fn generate(&self, chunk_coord: Coord) -> ChunkData<X, Y> {
+        self(chunk_coord)
+    }
+}
+
+/// Fills every chunk with one block.
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub struct Constant(pub BlockID);
+
+impl<const X: usize, const Y: usize> ChunkGenerator<X, Y> for Constant {
+    fn generate(&self, _: Coord) -> ChunkData<X, Y> {
+        ChunkData::new(self.0)
+    }
+}
+
+/// Alternates `even` and `odd` cells by the parity of their world `x + y`, so the pattern
+/// stays continuous across chunk borders.
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub struct Checkerboard {
+    pub even: BlockID,
+    pub odd: BlockID,
+}
+
+impl<const X: usize, const Y: usize> ChunkGenerator<X, Y> for Checkerboard {
+    fn generate(&self, chunk_coord: Coord) -> ChunkData<X, Y> {
+        let mut chunk = ChunkData::default();
+        for (local, cell) in chunk.iter_mut() {
+            let world = World::<X, Y>::to_world(chunk_coord, local);
+            *cell = if (world.0 + world.1).rem_euclid(2) == 0 {
+                self.even
+            } else {
+                self.odd
+            };
+        }
+        chunk
+    }
+}
+
+/// Terrain from a surface function: `height(x)` is the world `y` of the surface in column `x`.
+/// That cell and everything south of it are `ground`, the rest `air`.
+#[derive(Clone, Copy, Debug)]
+pub struct Heightmap<F> {
+    pub height: F,
+    pub ground: BlockID,
+    pub air: BlockID,
+}
+
+impl<F: Fn(isize) -> isize> Heightmap<F> {
+    pub fn new(height: F, ground: BlockID, air: BlockID) -> Self {
+        Self {
+            height,
+            ground,
+            air,
+        }
+    }
+}
+
+impl<F: Fn(isize) -> isize, const X: usize, const Y: usize> ChunkGenerator<X, Y> for Heightmap<F> {
+    fn generate(&self, chunk_coord: Coord) -> ChunkData<X, Y> {
+        let mut chunk = ChunkData::default();
+        for x in 0..X {
+            let origin = World::<X, Y>::to_world(chunk_coord, Coord(x as isize, 0));
+            let surface = (self.height)(origin.0);
+            for y in 0..Y {
+                chunk[y][x] = if origin.1 + y as isize >= surface {
+                    self.ground
+                } else {
+                    self.air
+                };
+            }
+        }
+        chunk
+    }
+}
+
+type Hook<const X: usize, const Y: usize> = Box<dyn Fn(Coord, &mut ChunkData<X, Y>)>;
+
+/// A world that fills in missing chunks from a generator the first time they are accessed.
+///
+/// Freshly generated chunks go through every hook, in the order they were added, before they
+/// are stored. Chunks inserted into the underlying world directly are never passed to hooks.
+pub struct LazyWorld<G, const X: usize, const Y: usize> {
+    world: World<X, Y>,
+    generator: G,
+    hooks: Vec<Hook<X, Y>>,
+}
+
+impl<G: ChunkGenerator<X, Y>, const X: usize, const Y: usize> LazyWorld<G, X, Y> {
+    pub fn new(generator: G) -> Self {
+        Self::with_world(World::new(), generator)
+    }
+
+    pub fn with_world(world: World<X, Y>, generator: G) -> Self {
+        Self {
+            world,
+            generator,
+            hooks: Vec::new(),
+        }
+    }
+
+    /// Adds a pass run on each chunk after it is generated, e.g. to place structures or ores.
+    pub fn add_hook(&mut self, hook: impl Fn(Coord, &mut ChunkData<X, Y>) + 'static) {
+        self.hooks.push(Box::new(hook));
+    }
+
+    pub fn generator(&self) -> &G {
+        &self.generator
+    }
+
+    /// The chunks generated or inserted so far.
+    pub fn world(&self) -> &World<X, Y> {
+        &self.world
+    }
+
+    pub fn world_mut(&mut self) -> &mut World<X, Y> {
+        &mut self.world
+    }
+
+    pub fn into_world(self) -> World<X, Y> {
+        self.world
+    }
+
+    /// Generates the chunk at `chunk_coord` if it isn't loaded, marking only new chunks dirty.
+    fn load(&mut self, chunk_coord: Coord) -> &mut Arc<ChunkData<X, Y>> {
+        let Self {
+            world,
+            generator,
+            hooks,
+        } = self;
+        let dirty = &mut world.dirty;
+        world.chunks.entry(chunk_coord).or_insert_with(|| {
+            let mut chunk = generator.generate(chunk_coord);
+            for hook in hooks.iter() {
+                hook(chunk_coord, &mut chunk);
+            }
+            dirty.insert(chunk_coord);
+            Arc::new(chunk)
+        })
+    }
+
+    /// The chunk at `chunk_coord`, generating it first if it isn't loaded. Marks it dirty like
+    /// `World::get_chunk_mut`.
+    pub fn chunk(&mut self, chunk_coord: Coord) -> &mut ChunkData<X, Y> {
+        self.world.dirty.insert(chunk_coord);
+        Arc::make_mut(self.load(chunk_coord))
+    }
+
+    pub fn get_block(&mut self, world: Coord) -> BlockID {
+        let local = World::<X, Y>::local_offset(world);
+        self.load(World::<X, Y>::chunk_at(world))[local.1 as usize][local.0 as usize]
+    }
+
+    /// Sets the block at `world`, returning the previous one.
+    pub fn set_block(&mut self, world: Coord, block: BlockID) -> BlockID {
+        let local = World::<X, Y>::local_offset(world);
+        let chunk = self.chunk(World::<X, Y>::chunk_at(world));
+        std::mem::replace(&mut chunk[local.1 as usize][local.0 as usize], block)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+
+    use super::*;
+
+    #[test]
+    fn chunks_are_generated_once_on_first_access() {
+        let calls = Cell::new(0);
+        let mut lazy = LazyWorld::<_, 2, 2>::new(|coord: Coord| {
+            calls.set(calls.get() + 1);
+            ChunkData::new(coord.x() as BlockID)
+        });
+        assert!(lazy.world().is_empty());
+        assert_eq!(lazy.get_block(Coord(5, 1)), 2);
+        assert_eq!(calls.get(), 1);
+        assert_eq!(lazy.get_block(Coord(4, 0)), 2);
+        assert_eq!(lazy.set_block(Coord(4, 1), 7), 2);
+        assert_eq!(lazy.get_block(Coord(4, 1)), 7);
+        assert_eq!(calls.get(), 1);
+        assert_eq!(lazy.get_block(Coord(0, 0)), 0);
+        assert_eq!(calls.get(), 2);
+        assert_eq!(lazy.world().len(), 2);
+    }
+
+    #[test]
+    fn generation_marks_only_new_chunks_dirty() {
+        let mut world = World::<2, 2>::new();
+        world.insert_chunk(Coord(0, 0), ChunkData::new(9));
+        world.take_dirty().for_each(drop);
+        let mut lazy = LazyWorld::with_world(world, Constant(1));
+        assert_eq!(lazy.get_block(Coord(1, 1)), 9);
+        assert_eq!(lazy.world().dirty_len(), 0);
+        assert_eq!(lazy.get_block(Coord(2, 0)), 1);
+        assert!(lazy.world().is_dirty(Coord(1, 0)));
+        assert_eq!(lazy.world().dirty_len(), 1);
+        lazy.chunk(Coord(0, 0));
+        assert!(lazy.world().is_dirty(Coord(0, 0)));
+    }
+
+    #[test]
+    fn hooks_run_on_generated_chunks_only() {
+        let mut world = World::<2, 2>::new();
+        world.insert_chunk(Coord(0, 0), ChunkData::new(9));
+        let mut lazy = LazyWorld::with_world(world, Constant(1));
+        lazy.add_hook(|coord, chunk| chunk[0][0] = coord.y() as BlockID + 10);
+        lazy.add_hook(|_, chunk| chunk[0][0] += 1);
+        assert_eq!(lazy.get_block(Coord(0, 2)), 12);
+        assert_eq!(lazy.get_block(Coord(0, 0)), 9);
+    }
+
+    #[test]
+    fn built_in_generators() {
+        let board: ChunkData<2, 2> = Checkerboard { even: 1, odd: 2 }.generate(Coord(1, 0));
+        assert_eq!(board.as_array(), &[[1, 2], [2, 1]]);
+        let terrain = Heightmap::new(|x| x, 5, 0);
+        let chunk: ChunkData<3, 3> = terrain.generate(Coord(0, 0));
+        assert_eq!(chunk.as_array(), &[[5, 0, 0], [5, 5, 0], [5, 5, 5]]);
+    }
+}
@@ -1,6 +1,35 @@
-use std::{collections::HashMap, convert::TryFrom, fmt, marker::PhantomData, ops::{Add, Div, Index, IndexMut, Mul, Rem}, str::FromStr, usize};
+use std::{cmp::Ordering, collections::{hash_map, HashMap, HashSet}, convert::TryFrom, error, fmt, io::{self, Read, Write}, iter, marker::PhantomData, ops::{Add, Div, Index, IndexMut, Mul, Rem}, slice, str::FromStr, sync::Arc};
 
-use serde::{Deserialize, Deserializer, Serialize, Serializer, de::{Error, SeqAccess, Unexpected, Visitor, value::U8Deserializer}, ser::SerializeTuple};
+use serde::{Deserialize, Deserializer, Serialize, Serializer, de::{Error, IgnoredAny, SeqAccess, Visitor}, ser::SerializeTuple};
+
+mod cache;
+mod chunk3d;
+mod delta;
+mod entity;
+mod generate;
+mod palette;
+pub mod region;
+mod registry;
+mod rle;
+mod structure;
+#[cfg(test)]
+mod test_value;
+mod tracked;
+mod view;
+mod wire;
+
+pub use cache::{CacheStats, ChunkCache};
+pub use chunk3d::ChunkData3D;
+pub use delta::ChunkDelta;
+pub use entity::{EntityChunk, EntityEntry, EntityError, EntityId};
+pub use generate::{Checkerboard, ChunkGenerator, Constant, Heightmap, LazyWorld};
+pub use palette::{PaletteError, PalettedChunk};
+pub use registry::{BlockDefinition, BlockRegistry, RegistryError};
+pub use rle::DecodeError;
+pub use structure::{StampMode, Structure};
+pub use tracked::TrackedChunk;
+pub use view::{BoxedChunk, ChunkView, ChunkViewMut};
+pub use wire::{IdRangeError, IdWidth};
 
 pub type BlockID = usize;
 
@@ -9,6 +38,120 @@ pub trait DeSerializable<'de>: Sized {
     fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error>;
 }
 
+#[doc(hidden)]
+pub mod __private {
+    use std::marker::PhantomData;
+
+    use serde::{Deserialize, Serialize};
+
+    pub use serde::{
+        de::{Error, IgnoredAny, SeqAccess, Visitor},
+        ser::SerializeTuple,
+        Deserializer, Serializer,
+    };
+
+    use crate::DeSerializable;
+
+    pub struct SerializeWith<'a, 'de, T>(&'a T, PhantomData<&'de ()>);
+
+    impl<'a, 'de, T> SerializeWith<'a, 'de, T> {
+        pub fn new(value: &'a T) -> Self {
+            Self(value, PhantomData)
+        }
+    }
+
+    impl<'a, 'de, T: DeSerializable<'de>> Serialize for SerializeWith<'a, 'de, T> {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            self.0.serialize(serializer)
+        }
+    }
+
+    pub struct DeserializeWith<T>(pub T);
+
+    impl<'de, T: DeSerializable<'de>> Deserialize<'de> for DeserializeWith<T> {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            T::deserialize(deserializer).map(DeserializeWith)
+        }
+    }
+}
+
+/// Defines a struct and implements `DeSerializable` for it, encoding its fields as a tuple in
+/// declaration order. Every field type must itself implement `DeSerializable`.
+#[macro_export]
+macro_rules! deserializable {
+    (
+        $(#[$meta:meta])*
+        $vis:vis struct $name:ident {
+            $($(#[$field_meta:meta])* $field_vis:vis $field:ident : $ty:ty),* $(,)?
+        }
+    ) => {
+        $(#[$meta])*
+        $vis struct $name {
+            $($(#[$field_meta])* $field_vis $field: $ty),*
+        }
+
+        impl<'de> $crate::DeSerializable<'de> for $name {
+            fn serialize<S: $crate::__private::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                use $crate::__private::SerializeTuple;
+                let mut tuple =
+                    serializer.serialize_tuple($crate::deserializable!(@count $($field)*))?;
+                $(tuple.serialize_element(
+                    &$crate::__private::SerializeWith::<'_, 'de, $ty>::new(&self.$field),
+                )?;)*
+                tuple.end()
+            }
+
+            fn deserialize<D: $crate::__private::Deserializer<'de>>(
+                deserializer: D,
+            ) -> Result<Self, D::Error> {
+                struct FieldsVisitor;
+
+                impl<'de> $crate::__private::Visitor<'de> for FieldsVisitor {
+                    type Value = $name;
+
+                    fn expecting(&self, formatter: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+                        write!(formatter, "a tuple of {} fields", $crate::deserializable!(@count $($field)*))
+                    }
+
+                    #[allow(unused_assignments, unused_mut, unused_variables)]
+                    fn visit_seq<A: $crate::__private::SeqAccess<'de>>(
+                        self,
+                        mut seq: A,
+                    ) -> Result<$name, A::Error> {
+                        let mut index = 0;
+                        $(
+                            let $field = seq
+                                .next_element::<$crate::__private::DeserializeWith<$ty>>()?
+                                .ok_or_else(|| {
+                                    <A::Error as $crate::__private::Error>::invalid_length(index, &self)
+                                })?
+                                .0;
+                            index += 1;
+                        )*
+                        if seq
+                            .next_element::<$crate::__private::IgnoredAny>()?
+                            .is_some()
+                        {
+                            return Err(<A::Error as $crate::__private::Error>::invalid_length(
+                                index + 1,
+                                &self,
+                            ));
+                        }
+                        Ok($name { $($field),* })
+                    }
+                }
+
+                deserializer.deserialize_tuple(
+                    $crate::deserializable!(@count $($field)*),
+                    FieldsVisitor,
+                )
+            }
+        }
+    };
+    (@count) => { 0usize };
+    (@count $head:ident $($tail:ident)*) => { 1usize + $crate::deserializable!(@count $($tail)*) };
+}
+
 impl<'de, T: Serialize + Deserialize<'de> + Default + Copy, const N: usize> DeSerializable<'de>
     for [T; N]
 {
@@ -39,6 +182,9 @@ impl<'de, T: Serialize + Deserialize<'de> + Default + Copy, const N: usize> DeSe
                         .next_element()?
                         .ok_or_else(|| A::Error::invalid_length(i, &self))?;
                 }
+                if seq.next_element::<IgnoredAny>()?.is_some() {
+                    return Err(A::Error::invalid_length(N + 1, &self));
+                }
                 Ok(arr)
             }
         }
@@ -48,122 +194,3675 @@ impl<'de, T: Serialize + Deserialize<'de> + Default + Copy, const N: usize> DeSe
     }
 }
 
-#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
-pub struct ChunkDataRow<const N: usize>(
-    #[serde(with = "DeSerializable")]
-    [BlockID; N]
-);
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub struct OutOfBoundsError;
 
-impl<const N: usize> ChunkDataRow<N> {
-    fn new(block: BlockID) -> Self {
-        Self([block; N])
+impl fmt::Display for OutOfBoundsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "index out of bounds")
     }
 }
 
-impl<const N: usize> Default for ChunkDataRow<N> {
-    fn default() -> Self {
-        ChunkDataRow([BlockID::default(); N])
-    }
+impl error::Error for OutOfBoundsError {}
+
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub struct LengthError {
+    pub expected: usize,
+    pub actual: usize,
 }
 
-impl<const N: usize> Index<usize> for ChunkDataRow<N> {
-    type Output = BlockID;
-    fn index(&self, index: usize) -> &Self::Output {
-        &self.0[index]
+impl fmt::Display for LengthError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "expected length {}, got {}", self.expected, self.actual)
     }
 }
 
-impl<const N: usize> IndexMut<usize> for ChunkDataRow<N> {
-    fn index_mut(&mut self, index: usize) -> &mut Self::Output {
-        &mut self.0[index]
+impl error::Error for LengthError {}
+
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum ChunkError {
+    OutOfBounds { x: usize, y: usize },
+}
+
+impl fmt::Display for ChunkError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ChunkError::OutOfBounds { x, y } => write!(f, "cell ({}, {}) is out of bounds", x, y),
+        }
     }
 }
 
-#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
-pub struct ChunkData<const X: usize, const Y: usize>(
+impl error::Error for ChunkError {}
+
+/// One row of a chunk, holding `N` cells of type `T`.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash, Debug)]
+#[serde(bound = "T: Serialize + serde::de::DeserializeOwned + Default + Copy")]
+// `ChunkData::as_array` depends on this layout.
+#[repr(transparent)]
+pub struct ChunkDataRow<const N: usize, T = BlockID>(
     #[serde(with = "DeSerializable")]
-    [ChunkDataRow<X>; Y],
+    [T; N]
 );
 
-impl<const X: usize, const Y: usize> ChunkData<X, Y> {
-    pub fn new(block: BlockID) -> Self {
-        Self([ChunkDataRow::new(block); Y])
+impl<const N: usize, T: Copy> ChunkDataRow<N, T> {
+    fn new(value: T) -> Self {
+        Self([value; N])
+    }
+
+    pub fn fill(&mut self, value: T) {
+        self.0 = [value; N];
+    }
+
+    pub fn contains(&self, value: T) -> bool
+    where
+        T: PartialEq,
+    {
+        self.0.contains(&value)
+    }
+
+    /// Overwrites the cells `start..start + values.len()`.
+    pub fn splice(&mut self, start: usize, values: &[T]) -> Result<(), OutOfBoundsError> {
+        let end = start.checked_add(values.len()).ok_or(OutOfBoundsError)?;
+        self.0
+            .get_mut(start..end)
+            .ok_or(OutOfBoundsError)?
+            .copy_from_slice(values);
+        Ok(())
     }
 }
 
-impl<const X: usize, const Y: usize> Default for ChunkData<X, Y> {
+impl<const N: usize, T: Copy + Default> Default for ChunkDataRow<N, T> {
     fn default() -> Self {
-        Self([ChunkDataRow::default(); Y])
+        ChunkDataRow([T::default(); N])
     }
 }
 
-impl<const X: usize, const Y: usize> Index<usize> for ChunkData<X, Y> {
-    type Output = ChunkDataRow<X>;
+impl<const N: usize, T> Index<usize> for ChunkDataRow<N, T> {
+    type Output = T;
     fn index(&self, index: usize) -> &Self::Output {
         &self.0[index]
     }
 }
 
-impl<const X: usize, const Y: usize> IndexMut<usize> for ChunkData<X, Y> {
+impl<const N: usize, T> IndexMut<usize> for ChunkDataRow<N, T> {
     fn index_mut(&mut self, index: usize) -> &mut Self::Output {
         &mut self.0[index]
     }
 }
 
-#[derive(PartialEq, Eq, Hash, Clone, Copy, Serialize, Deserialize, Debug)]
-pub struct Coord(isize, isize);
+/// Serde `with` helper for `ChunkDataRow` that writes a row made of a single block as the
+/// pair `(block, N)` instead of listing every cell.
+///
+/// Both forms are plain sequences told apart by length, so the pair is only used when `N > 2`;
+/// shorter rows are always written in full.
+pub mod compact_row {
+    use std::fmt;
 
-impl Coord {
-    pub fn new(x: isize, y: isize) -> Self {
-        Self(x, y)
+    use serde::{
+        de::{Error, SeqAccess, Visitor},
+        ser::SerializeSeq,
+        Deserializer, Serializer,
+    };
+
+    use crate::{BlockID, ChunkDataRow};
+
+    pub fn serialize<S: Serializer, const N: usize>(
+        row: &ChunkDataRow<N>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        match row.0.first() {
+            Some(&block) if N > 2 && row.0.iter().all(|&b| b == block) => {
+                let mut seq = serializer.serialize_seq(Some(2))?;
+                seq.serialize_element(&block)?;
+                seq.serialize_element(&N)?;
+                seq.end()
+            }
+            _ => {
+                let mut seq = serializer.serialize_seq(Some(N))?;
+                for block in &row.0 {
+                    seq.serialize_element(block)?;
+                }
+                seq.end()
+            }
+        }
     }
 
-    pub fn x(&self) -> isize {
-        self.0
+    struct RowVisitor<const N: usize>;
+
+    impl<'de, const N: usize> Visitor<'de> for RowVisitor<N> {
+        type Value = ChunkDataRow<N>;
+
+        fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            write!(f, "a row of {} blocks or a (block, {}) pair", N, N)
+        }
+
+        fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+            let mut cells = [BlockID::default(); N];
+            let mut len = 0;
+            while let Some(value) = seq.next_element()? {
+                if len == N {
+                    return Err(A::Error::invalid_length(len + 1, &self));
+                }
+                cells[len] = value;
+                len += 1;
+            }
+            match len {
+                _ if len == N => Ok(ChunkDataRow(cells)),
+                2 if N > 2 && cells[1] == N => Ok(ChunkDataRow::new(cells[0])),
+                2 if N > 2 => Err(A::Error::invalid_length(cells[1], &self)),
+                _ => Err(A::Error::invalid_length(len, &self)),
+            }
+        }
     }
 
-    pub fn y(&self) -> isize {
-        self.1
+    pub fn deserialize<'de, D: Deserializer<'de>, const N: usize>(
+        deserializer: D,
+    ) -> Result<ChunkDataRow<N>, D::Error> {
+        deserializer.deserialize_seq(RowVisitor)
     }
 }
 
-impl fmt::Display for Coord {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "({}, {})", self.x(), self.y())
+/// A `X x Y` grid of cells of type `T`, stored as rows and indexed as `chunk[y][x]`.
+///
+/// `T` defaults to `BlockID`, and most of the block-specific methods are only available for
+/// that case.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash, Debug)]
+#[serde(bound = "T: Serialize + serde::de::DeserializeOwned + Default + Copy")]
+#[repr(transparent)]
+pub struct ChunkData<const X: usize, const Y: usize, T = BlockID>(
+    #[serde(with = "DeSerializable")]
+    [ChunkDataRow<X, T>; Y],
+);
+
+pub type BlockChunk<const X: usize, const Y: usize> = ChunkData<X, Y, BlockID>;
+
+impl<const X: usize, const Y: usize, T: Copy> ChunkData<X, Y, T> {
+    pub fn new(value: T) -> Self {
+        Self([ChunkDataRow::new(value); Y])
     }
-}
 
-impl Add<Coord> for Coord {
-    type Output = Self;
-    fn add(self, rhs: Coord) -> Self::Output {
-        Self(self.0 + rhs.0, self.1 + rhs.1)
+    pub fn fill(&mut self, value: T) {
+        for row in &mut self.0 {
+            row.fill(value);
+        }
     }
-}
 
-impl Mul<Coord> for Coord {
-    type Output = Self;
-    fn mul(self, rhs: Coord) -> Self::Output {
-        Self(self.0 * rhs.0, self.1 * rhs.1)
+    pub fn as_array(&self) -> &[[T; X]; Y] {
+        // A reference to the rows can't be turned into a reference to their arrays without a
+        // cast, since no `[[T; X]; Y]` exists to borrow.
+        //
+        // SAFETY: `ChunkDataRow<X, T>` is `repr(transparent)` over `[T; X]`, so it has the same
+        // size, alignment and bit validity, and arrays of the two have the same layout too. The
+        // returned reference borrows `self`, so it can't outlive or alias the rows.
+        unsafe { &*(&self.0 as *const [ChunkDataRow<X, T>; Y] as *const [[T; X]; Y]) }
     }
-}
 
-impl Div<Coord> for Coord {
-    type Output = Self;
-    fn div(self, rhs: Coord) -> Self::Output {
-        Self(self.0 / rhs.0, self.1 / rhs.1)
+    pub fn as_array_mut(&mut self) -> &mut [[T; X]; Y] {
+        // SAFETY: as in `as_array`. `ChunkDataRow` puts no invariant on its cells, so any
+        // `[T; X]` written through the returned reference is a valid row, and the unique borrow
+        // of `self` means nothing else can observe the rows meanwhile.
+        unsafe { &mut *(&mut self.0 as *mut [ChunkDataRow<X, T>; Y] as *mut [[T; X]; Y]) }
     }
-}
 
-impl Div<isize> for Coord {
-    type Output = Self;
-    fn div(self, rhs: isize) -> Self::Output {
-        Self(self.0 / rhs, self.1 / rhs)
+    pub fn row(&self, y: usize) -> Option<&ChunkDataRow<X, T>> {
+        self.0.get(y)
+    }
+
+    pub fn row_mut(&mut self, y: usize) -> Option<&mut ChunkDataRow<X, T>> {
+        self.0.get_mut(y)
+    }
+
+    /// Cells and their coordinates in row-major order.
+    pub fn iter(&self) -> Cells<'_, X, T> {
+        Cells {
+            inner: self.as_array().as_flattened().iter().enumerate(),
+        }
+    }
+
+    pub fn iter_mut(&mut self) -> CellsMut<'_, X, T> {
+        CellsMut {
+            inner: self
+                .as_array_mut()
+                .as_flattened_mut()
+                .iter_mut()
+                .enumerate(),
+        }
+    }
+
+    /// Like `iter`, but yields the cells by value.
+    pub fn enumerate_cells(&self) -> impl Iterator<Item = (Coord, T)> + '_ {
+        self.iter().map(|(coord, &value)| (coord, value))
+    }
+
+    pub fn rows(&self) -> slice::Iter<'_, ChunkDataRow<X, T>> {
+        self.0.iter()
+    }
+
+    pub fn rows_mut(&mut self) -> slice::IterMut<'_, ChunkDataRow<X, T>> {
+        self.0.iter_mut()
+    }
+
+    /// The orthogonal neighbours of `local` that lie inside the chunk.
+    pub fn neighbors(&self, local: Coord) -> impl Iterator<Item = Coord> {
+        IntoIterator::into_iter(local.neighbors4())
+            .filter(|coord| coord.to_usize().is_some_and(|(x, y)| x < X && y < Y))
+    }
+
+    /// The cells matching `predicate` that are orthogonally connected to `start`, beginning with
+    /// `start` itself. Empty if `start` is outside the chunk or does not match.
+    pub fn flood_fill(&self, start: Coord, predicate: impl Fn(T) -> bool) -> Vec<Coord> {
+        let mut region = Vec::new();
+        match start.to_usize() {
+            Some((x, y)) if x < X && y < Y && predicate(self[y][x]) => {}
+            _ => return region,
+        }
+        let mut visited = vec![false; X * Y];
+        let mut stack = vec![start];
+        visited[start.1 as usize * X + start.0 as usize] = true;
+        while let Some(coord) = stack.pop() {
+            region.push(coord);
+            for next in self.neighbors(coord) {
+                let (x, y) = (next.0 as usize, next.1 as usize);
+                if !visited[y * X + x] && predicate(self[y][x]) {
+                    visited[y * X + x] = true;
+                    stack.push(next);
+                }
+            }
+        }
+        region
+    }
+
+    pub fn get(&self, x: usize, y: usize) -> Option<&T> {
+        self.0.get(y)?.0.get(x)
+    }
+
+    pub fn get_mut(&mut self, x: usize, y: usize) -> Option<&mut T> {
+        self.0.get_mut(y)?.0.get_mut(x)
+    }
+
+    pub fn set(&mut self, x: usize, y: usize, value: T) -> Result<(), ChunkError> {
+        *self.get_mut(x, y).ok_or(ChunkError::OutOfBounds { x, y })? = value;
+        Ok(())
+    }
+
+    /// The value shared by every cell, or `None` if any two cells differ.
+    pub fn is_uniform(&self) -> Option<T>
+    where
+        T: PartialEq,
+    {
+        let mut cells = self.iter();
+        let (_, &first) = cells.next()?;
+        cells.all(|(_, &value)| value == first).then_some(first)
     }
 }
 
-impl Rem<Coord> for Coord {
-    type Output = Self;
-    fn rem(self, rhs: Self) -> Self::Output {
-        Self(self.0 % rhs.0, self.1 % rhs.1)
+impl<const X: usize, const Y: usize> ChunkData<X, Y> {
+    /// FNV-1a hash of the block ids in row-major order, stable across runs and platforms.
+    pub fn checksum(&self) -> u64 {
+        fnv1a(self.as_array().iter().flatten())
+    }
+
+    /// `checksum` of each `T x T` tile, in row-major tile order.
+    ///
+    /// # Panics
+    ///
+    /// Panics unless `T` is non-zero and divides both `X` and `Y`.
+    pub fn tile_checksums<const T: usize>(&self) -> Vec<u64> {
+        assert!(
+            T != 0 && X.is_multiple_of(T) && Y.is_multiple_of(T),
+            "tile size must divide the chunk dimensions"
+        );
+        let rows = self.as_array();
+        let mut checksums = Vec::with_capacity((X / T) * (Y / T));
+        for ty in (0..Y).step_by(T) {
+            for tx in (0..X).step_by(T) {
+                checksums.push(fnv1a(
+                    rows[ty..ty + T].iter().flat_map(|row| &row[tx..tx + T]),
+                ));
+            }
+        }
+        checksums
+    }
+
+    /// Sets every cell selected by `mask` to `block`, returning how many cells changed.
+    pub fn apply_mask(&mut self, mask: &[[bool; X]; Y], block: BlockID) -> usize {
+        let mut changed = 0;
+        for (row, mask_row) in self.as_array_mut().iter_mut().zip(mask) {
+            for (cell, &selected) in row.iter_mut().zip(mask_row) {
+                if selected && *cell != block {
+                    *cell = block;
+                    changed += 1;
+                }
+            }
+        }
+        changed
+    }
+
+    /// Tight bounding box of all non-default cells in local coordinates.
+    pub fn content_bounds(&self) -> Option<Aabb> {
+        let mut bounds: Option<Aabb> = None;
+        for (y, row) in self.as_array().iter().enumerate() {
+            for (x, &block) in row.iter().enumerate() {
+                if block == BlockID::default() {
+                    continue;
+                }
+                let cell = Aabb::new(Coord(x as isize, y as isize), Coord(x as isize, y as isize));
+                bounds = Some(match bounds {
+                    None => cell,
+                    Some(bounds) => bounds.union(&cell),
+                });
+            }
+        }
+        bounds
+    }
+
+    pub fn changed_rows(&self, other: &ChunkData<X, Y>) -> Vec<usize> {
+        self.0
+            .iter()
+            .zip(&other.0)
+            .enumerate()
+            .filter(|(_, (a, b))| a != b)
+            .map(|(y, _)| y)
+            .collect()
+    }
+
+    /// Maximal horizontal runs of identical cells as `(start, length, block)`, never crossing rows.
+    pub fn iter_runs(&self) -> impl Iterator<Item = (Coord, usize, BlockID)> + '_ {
+        self.as_array().iter().enumerate().flat_map(|(y, row)| {
+            let mut x = 0;
+            iter::from_fn(move || {
+                let block = *row.get(x)?;
+                let start = x;
+                while x < X && row[x] == block {
+                    x += 1;
+                }
+                Some((Coord(start as isize, y as isize), x - start, block))
+            })
+        })
+    }
+
+    /// Whether every row reads the same left-to-right and right-to-left.
+    pub fn is_horizontally_symmetric(&self) -> bool {
+        self.as_array()
+            .iter()
+            .all(|row| (0..X / 2).all(|x| row[x] == row[X - 1 - x]))
+    }
+
+    /// Whether the rows read the same top-to-bottom and bottom-to-top.
+    pub fn is_vertically_symmetric(&self) -> bool {
+        (0..Y / 2).all(|y| self.0[y] == self.0[Y - 1 - y])
+    }
+
+    pub fn contains(&self, id: BlockID) -> bool {
+        self.0.iter().any(|row| row.contains(id))
+    }
+
+    pub fn count_where<F: Fn(Coord, BlockID) -> bool>(&self, pred: F) -> usize {
+        let mut count = 0;
+        for (y, row) in self.as_array().iter().enumerate() {
+            for (x, &block) in row.iter().enumerate() {
+                if pred(Coord(x as isize, y as isize), block) {
+                    count += 1;
+                }
+            }
+        }
+        count
+    }
+
+    /// Shifts cells by `(dx, dy)`. Vacated cells are set to `fill`, or wrap around when it is `None`.
+    pub fn scroll(&mut self, dx: isize, dy: isize, fill: Option<BlockID>) {
+        let source = *self.as_array();
+        for (y, row) in self.as_array_mut().iter_mut().enumerate() {
+            for (x, cell) in row.iter_mut().enumerate() {
+                let (sx, sy) = (x as isize - dx, y as isize - dy);
+                *cell = match fill {
+                    None => {
+                        source[sy.rem_euclid(Y as isize) as usize]
+                            [sx.rem_euclid(X as isize) as usize]
+                    }
+                    Some(block) => {
+                        if (0..X as isize).contains(&sx) && (0..Y as isize).contains(&sy) {
+                            source[sy as usize][sx as usize]
+                        } else {
+                            block
+                        }
+                    }
+                };
+            }
+        }
+    }
+
+    /// Whether the row-major flattening of the chunk equals `expected`.
+    pub fn eq_flat(&self, expected: &[BlockID]) -> bool {
+        expected.len() == X * Y && self.as_array().iter().flatten().eq(expected)
+    }
+
+    fn sub_chunk<const W: usize, const H: usize>(&self, x0: usize, y0: usize) -> ChunkData<W, H> {
+        let mut sub = ChunkData::<W, H>::default();
+        for (y, row) in sub.as_array_mut().iter_mut().enumerate() {
+            row.copy_from_slice(&self.as_array()[y0 + y][x0..x0 + W]);
+        }
+        sub
+    }
+
+    /// Splits the chunk into its `[NW, NE, SW, SE]` quarters, or `None` unless `W * 2 == X` and
+    /// `H * 2 == Y`.
+    pub fn quadrants<const W: usize, const H: usize>(&self) -> Option<[ChunkData<W, H>; 4]> {
+        if W * 2 != X || H * 2 != Y {
+            return None;
+        }
+        Some([
+            self.sub_chunk(0, 0),
+            self.sub_chunk(W, 0),
+            self.sub_chunk(0, H),
+            self.sub_chunk(W, H),
+        ])
+    }
+
+    /// Groups of 4-connected cells satisfying `is_member`, ordered by their first cell in
+    /// row-major order.
+    pub fn connected_components(&self, is_member: impl Fn(BlockID) -> bool) -> Vec<Vec<Coord>> {
+        let cells = self.as_array();
+        let mut visited = vec![false; X * Y];
+        let mut components = Vec::new();
+        for start in 0..X * Y {
+            if visited[start] || !is_member(cells[start / X][start % X]) {
+                continue;
+            }
+            visited[start] = true;
+            let mut component = Vec::new();
+            let mut stack = vec![(start % X, start / X)];
+            while let Some((x, y)) = stack.pop() {
+                component.push(Coord(x as isize, y as isize));
+                let neighbours = [
+                    (x.wrapping_sub(1), y),
+                    (x + 1, y),
+                    (x, y.wrapping_sub(1)),
+                    (x, y + 1),
+                ];
+                for &(nx, ny) in &neighbours {
+                    if nx < X && ny < Y && !visited[ny * X + nx] && is_member(cells[ny][nx]) {
+                        visited[ny * X + nx] = true;
+                        stack.push((nx, ny));
+                    }
+                }
+            }
+            components.push(component);
+        }
+        components
+    }
+
+    /// Scales the chunk down by the factor `X / W`, which must also equal `Y / H`. Each output
+    /// cell is the most common block in its region, ties going to the smallest id.
+    pub fn downsample<const W: usize, const H: usize>(&self) -> Option<ChunkData<W, H>> {
+        if W == 0 || H == 0 || !X.is_multiple_of(W) || X / W * H != Y {
+            return None;
+        }
+        let f = X / W;
+        let mut out = ChunkData::<W, H>::default();
+        let mut region = Vec::with_capacity(f * f);
+        for (oy, row) in out.as_array_mut().iter_mut().enumerate() {
+            for (ox, cell) in row.iter_mut().enumerate() {
+                region.clear();
+                for source_row in &self.as_array()[oy * f..(oy + 1) * f] {
+                    region.extend_from_slice(&source_row[ox * f..(ox + 1) * f]);
+                }
+                region.sort_unstable();
+                let (mut best, mut best_len) = (region[0], 0);
+                for run in region.chunk_by(|a, b| a == b) {
+                    if run.len() > best_len {
+                        best = run[0];
+                        best_len = run.len();
+                    }
+                }
+                *cell = best;
+            }
+        }
+        Some(out)
+    }
+
+    /// Rotates 90 degrees clockwise (with `y` pointing south), swapping the dimensions.
+    pub fn rotate_cw(&self) -> ChunkData<Y, X> {
+        let mut out = ChunkData::<Y, X>::default();
+        for (y, row) in self.as_array().iter().enumerate() {
+            for (x, &block) in row.iter().enumerate() {
+                out[x][Y - 1 - y] = block;
+            }
+        }
+        out
+    }
+
+    /// Rotates 90 degrees counter-clockwise (with `y` pointing south), swapping the dimensions.
+    pub fn rotate_ccw(&self) -> ChunkData<Y, X> {
+        let mut out = ChunkData::<Y, X>::default();
+        for (y, row) in self.as_array().iter().enumerate() {
+            for (x, &block) in row.iter().enumerate() {
+                out[X - 1 - x][y] = block;
+            }
+        }
+        out
+    }
+
+    /// Copies the chunk into a `W x H` chunk of `fill` at `offset`, or `None` if it doesn't fit.
+    pub fn pad<const W: usize, const H: usize>(
+        &self,
+        offset: Coord,
+        fill: BlockID,
+    ) -> Option<ChunkData<W, H>> {
+        let (x0, y0) = offset.to_usize()?;
+        if x0.checked_add(X)? > W || y0.checked_add(Y)? > H {
+            return None;
+        }
+        let mut out = ChunkData::<W, H>::new(fill);
+        for (y, row) in self.as_array().iter().enumerate() {
+            out.as_array_mut()[y0 + y][x0..x0 + X].copy_from_slice(row);
+        }
+        Some(out)
+    }
+
+    /// Sets every edge cell to `block`, leaving the interior untouched.
+    pub fn set_border(&mut self, block: BlockID) {
+        for (y, row) in self.as_array_mut().iter_mut().enumerate() {
+            if y == 0 || y + 1 == Y {
+                row.fill(block);
+            } else if X > 0 {
+                row[0] = block;
+                row[X - 1] = block;
+            }
+        }
+    }
+
+    /// Replaces each cell `v` with `palette[v]`. On `Err(v)` for an out-of-range index the chunk
+    /// is left unchanged.
+    pub fn apply_palette(&mut self, palette: &[BlockID]) -> Result<(), usize> {
+        if let Some(&bad) = self
+            .as_array()
+            .iter()
+            .flatten()
+            .find(|&&v| v >= palette.len())
+        {
+            return Err(bad);
+        }
+        for cell in self.as_array_mut().iter_mut().flatten() {
+            *cell = palette[*cell];
+        }
+        Ok(())
+    }
+
+    /// Mirrors the top-left `X / 2 x Y / 2` quadrant into the other three. With odd dimensions
+    /// the middle column or row is left untouched.
+    pub fn mirror_from_nw(&mut self) {
+        let cells = self.as_array_mut();
+        for y in 0..Y / 2 {
+            for x in 0..X / 2 {
+                let block = cells[y][x];
+                cells[y][X - 1 - x] = block;
+                cells[Y - 1 - y][x] = block;
+                cells[Y - 1 - y][X - 1 - x] = block;
+            }
+        }
+    }
+
+    /// Number of horizontally or vertically adjacent cell pairs with different blocks.
+    pub fn count_transitions(&self) -> usize {
+        let cells = self.as_array();
+        let horizontal: usize = cells
+            .iter()
+            .map(|row| row.windows(2).filter(|pair| pair[0] != pair[1]).count())
+            .sum();
+        let vertical: usize = cells
+            .windows(2)
+            .map(|rows| rows[0].iter().zip(&rows[1]).filter(|(a, b)| a != b).count())
+            .sum();
+        horizontal + vertical
+    }
+
+    pub fn fold_cells<B, F: FnMut(B, Coord, BlockID) -> B>(&self, init: B, mut f: F) -> B {
+        let mut acc = init;
+        for (y, row) in self.as_array().iter().enumerate() {
+            for (x, &block) in row.iter().enumerate() {
+                acc = f(acc, Coord(x as isize, y as isize), block);
+            }
+        }
+        acc
+    }
+
+    /// Writes the cell count as a little-endian `u64`, then every block id as a little-endian
+    /// `u64` in row-major order.
+    pub fn write_to<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        w.write_all(&((X * Y) as u64).to_le_bytes())?;
+        for &block in self.as_array().iter().flatten() {
+            w.write_all(&(block as u64).to_le_bytes())?;
+        }
+        Ok(())
+    }
+
+    /// Reads the `write_to` format one block id at a time, without buffering the whole payload.
+    pub fn read_streaming<R: Read>(r: &mut R) -> io::Result<Self> {
+        let mut buf = [0; 8];
+        r.read_exact(&mut buf)?;
+        if u64::from_le_bytes(buf) != (X * Y) as u64 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "chunk cell count mismatch",
+            ));
+        }
+        let mut chunk = Self::default();
+        for cell in chunk.as_array_mut().iter_mut().flatten() {
+            r.read_exact(&mut buf)?;
+            *cell = BlockID::try_from(u64::from_le_bytes(buf))
+                .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "block id out of range"))?;
+        }
+        Ok(chunk)
+    }
+
+    /// Every `W x W` window that fits in the chunk with its top-left coordinate, in row-major order.
+    pub fn windows<const W: usize>(&self) -> impl Iterator<Item = (Coord, [[BlockID; W]; W])> + '_ {
+        let cells = self.as_array();
+        let (nx, ny) = if W == 0 || W > X || W > Y {
+            (0, 0)
+        } else {
+            (X - W + 1, Y - W + 1)
+        };
+        (0..ny).flat_map(move |y| {
+            (0..nx).map(move |x| {
+                let mut window = [[BlockID::default(); W]; W];
+                for (dy, row) in window.iter_mut().enumerate() {
+                    row.copy_from_slice(&cells[y + dy][x..x + W]);
+                }
+                (Coord(x as isize, y as isize), window)
+            })
+        })
+    }
+
+    /// The `content_bounds` region as `(top_left, width, height, row-major blocks)`.
+    pub fn crop(&self) -> Option<(Coord, usize, usize, Vec<BlockID>)> {
+        let bounds = self.content_bounds()?;
+        let blocks = bounds
+            .iter()
+            .map(|c| self[c.1 as usize][c.0 as usize])
+            .collect();
+        Some((bounds.min, bounds.width(), bounds.height(), blocks))
+    }
+
+    /// Replaces the first row-major occurrence of `from`, returning where it was.
+    pub fn replace_first(&mut self, from: BlockID, to: BlockID) -> Option<Coord> {
+        for (y, row) in self.as_array_mut().iter_mut().enumerate() {
+            if let Some(x) = row.iter().position(|&block| block == from) {
+                row[x] = to;
+                return Some(Coord(x as isize, y as isize));
+            }
+        }
+        None
+    }
+
+    /// One bit per cell in row-major order, least significant bit first.
+    pub fn pack_bits(&self, is_set: impl Fn(BlockID) -> bool) -> Vec<u8> {
+        let mut bytes = vec![0; (X * Y).div_ceil(8)];
+        for (i, &block) in self.as_array().iter().flatten().enumerate() {
+            if is_set(block) {
+                bytes[i / 8] |= 1 << (i % 8);
+            }
+        }
+        bytes
+    }
+
+    /// Inverse of `pack_bits`, mapping set bits to `set` and clear bits to `unset`.
+    pub fn from_bits(bytes: &[u8], set: BlockID, unset: BlockID) -> Result<Self, LengthError> {
+        let expected = (X * Y).div_ceil(8);
+        if bytes.len() != expected {
+            return Err(LengthError {
+                expected,
+                actual: bytes.len(),
+            });
+        }
+        let mut chunk = Self::default();
+        for (i, cell) in chunk.as_array_mut().iter_mut().flatten().enumerate() {
+            *cell = if bytes[i / 8] & (1 << (i % 8)) != 0 {
+                set
+            } else {
+                unset
+            };
+        }
+        Ok(chunk)
     }
-}
\ No newline at end of file
+
+    pub fn to_paletted(&self) -> PalettedChunk<X, Y> {
+        PalettedChunk::from_chunk(self)
+    }
+}
+
+/// Serde `with` helper for `ChunkData` that writes all-default rows as `None` instead of a full
+/// row, shrinking sparse chunks in formats like JSON.
+pub mod sparse_rows {
+    use serde::{Deserializer, Serializer};
+
+    use crate::{ChunkData, ChunkDataRow, DeSerializable};
+
+    pub fn serialize<S: Serializer, const X: usize, const Y: usize>(
+        chunk: &ChunkData<X, Y>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        let mut rows = [None; Y];
+        for (dst, row) in rows.iter_mut().zip(&chunk.0) {
+            if *row != ChunkDataRow::default() {
+                *dst = Some(*row);
+            }
+        }
+        DeSerializable::serialize(&rows, serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>, const X: usize, const Y: usize>(
+        deserializer: D,
+    ) -> Result<ChunkData<X, Y>, D::Error> {
+        let rows: [Option<ChunkDataRow<X>>; Y] = DeSerializable::deserialize(deserializer)?;
+        let mut chunk = ChunkData::default();
+        for (dst, row) in chunk.0.iter_mut().zip(&rows) {
+            if let Some(row) = row {
+                *dst = *row;
+            }
+        }
+        Ok(chunk)
+    }
+}
+
+impl<const X: usize, const Y: usize, T: Copy + Default> Default for ChunkData<X, Y, T> {
+    fn default() -> Self {
+        Self([ChunkDataRow::default(); Y])
+    }
+}
+
+impl<const X: usize, const Y: usize, T: Copy> IntoIterator for ChunkData<X, Y, T> {
+    type Item = (Coord, T);
+    type IntoIter = ChunkDataIntoIter<X, Y, T>;
+    fn into_iter(self) -> Self::IntoIter {
+        ChunkDataIntoIter {
+            data: self,
+            index: 0,
+        }
+    }
+}
+
+/// Sets each `(coord, block)` pair, silently skipping coordinates outside the chunk.
+impl<const X: usize, const Y: usize, T> Extend<(Coord, T)> for ChunkData<X, Y, T> {
+    fn extend<I: IntoIterator<Item = (Coord, T)>>(&mut self, iter: I) {
+        for (coord, value) in iter {
+            if let Some((x, y)) = coord.to_usize().filter(|&(x, y)| x < X && y < Y) {
+                self[y][x] = value;
+            }
+        }
+    }
+}
+
+/// Consuming iterator over a chunk's cells in row-major order.
+pub struct ChunkDataIntoIter<const X: usize, const Y: usize, T = BlockID> {
+    data: ChunkData<X, Y, T>,
+    index: usize,
+}
+
+impl<const X: usize, const Y: usize, T: Copy> Iterator for ChunkDataIntoIter<X, Y, T> {
+    type Item = (Coord, T);
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.index >= X * Y {
+            return None;
+        }
+        let (x, y) = (self.index % X, self.index / X);
+        self.index += 1;
+        Some((Coord(x as isize, y as isize), self.data[y][x]))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining = X * Y - self.index;
+        (remaining, Some(remaining))
+    }
+}
+
+impl<const X: usize, const Y: usize, T: Copy> ExactSizeIterator for ChunkDataIntoIter<X, Y, T> {}
+
+impl<'a, const X: usize, const Y: usize, T: Copy> IntoIterator for &'a ChunkData<X, Y, T> {
+    type Item = (Coord, &'a T);
+    type IntoIter = Cells<'a, X, T>;
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<'a, const X: usize, const Y: usize, T: Copy> IntoIterator for &'a mut ChunkData<X, Y, T> {
+    type Item = (Coord, &'a mut T);
+    type IntoIter = CellsMut<'a, X, T>;
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter_mut()
+    }
+}
+
+fn cell_coord<const X: usize>(index: usize) -> Coord {
+    Coord((index % X) as isize, (index / X) as isize)
+}
+
+/// Iterator over a chunk's cells and their coordinates in row-major order.
+pub struct Cells<'a, const X: usize, T = BlockID> {
+    inner: iter::Enumerate<slice::Iter<'a, T>>,
+}
+
+impl<'a, const X: usize, T> Iterator for Cells<'a, X, T> {
+    type Item = (Coord, &'a T);
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner
+            .next()
+            .map(|(i, block)| (cell_coord::<X>(i), block))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<const X: usize, T> DoubleEndedIterator for Cells<'_, X, T> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.inner
+            .next_back()
+            .map(|(i, block)| (cell_coord::<X>(i), block))
+    }
+}
+
+impl<const X: usize, T> ExactSizeIterator for Cells<'_, X, T> {}
+
+/// Mutable iterator over a chunk's cells and their coordinates in row-major order.
+pub struct CellsMut<'a, const X: usize, T = BlockID> {
+    inner: iter::Enumerate<slice::IterMut<'a, T>>,
+}
+
+impl<'a, const X: usize, T> Iterator for CellsMut<'a, X, T> {
+    type Item = (Coord, &'a mut T);
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner
+            .next()
+            .map(|(i, block)| (cell_coord::<X>(i), block))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<const X: usize, T> DoubleEndedIterator for CellsMut<'_, X, T> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.inner
+            .next_back()
+            .map(|(i, block)| (cell_coord::<X>(i), block))
+    }
+}
+
+impl<const X: usize, T> ExactSizeIterator for CellsMut<'_, X, T> {}
+
+impl<const X: usize, const Y: usize, T> Index<usize> for ChunkData<X, Y, T> {
+    type Output = ChunkDataRow<X, T>;
+    fn index(&self, index: usize) -> &Self::Output {
+        &self.0[index]
+    }
+}
+
+impl<const X: usize, const Y: usize, T> IndexMut<usize> for ChunkData<X, Y, T> {
+    fn index_mut(&mut self, index: usize) -> &mut Self::Output {
+        &mut self.0[index]
+    }
+}
+
+/// Cardinal directions, with north pointing towards negative `y`.
+#[derive(PartialEq, Eq, Hash, Clone, Copy, Serialize, Deserialize, Debug)]
+pub enum Direction {
+    North,
+    East,
+    South,
+    West,
+}
+
+impl Direction {
+    pub fn offset(&self) -> Coord {
+        match self {
+            Direction::North => Coord(0, -1),
+            Direction::East => Coord(1, 0),
+            Direction::South => Coord(0, 1),
+            Direction::West => Coord(-1, 0),
+        }
+    }
+}
+
+/// Compass directions, with north pointing towards negative `y`.
+#[derive(PartialEq, Eq, Hash, Clone, Copy, Serialize, Deserialize, Debug)]
+pub enum Direction8 {
+    North,
+    NorthEast,
+    East,
+    SouthEast,
+    South,
+    SouthWest,
+    West,
+    NorthWest,
+}
+
+#[derive(PartialEq, Eq, Hash, Clone, Copy, Default, Serialize, Deserialize, Debug)]
+pub struct Coord(isize, isize);
+
+impl Coord {
+    pub fn new(x: isize, y: isize) -> Self {
+        Self(x, y)
+    }
+
+    pub fn x(&self) -> isize {
+        self.0
+    }
+
+    pub fn y(&self) -> isize {
+        self.1
+    }
+
+    pub fn to_usize(&self) -> Option<(usize, usize)> {
+        Some((usize::try_from(self.0).ok()?, usize::try_from(self.1).ok()?))
+    }
+
+    pub fn map<F: Fn(isize) -> isize>(&self, f: F) -> Coord {
+        Self(f(self.0), f(self.1))
+    }
+
+    /// Every coordinate within Manhattan distance `radius` of `self`, including `self`.
+    pub fn within_manhattan(&self, radius: usize) -> impl Iterator<Item = Coord> {
+        let center = *self;
+        let r = radius as isize;
+        (-r..=r).flat_map(move |dy| {
+            let span = r - dy.abs();
+            (-span..=span).map(move |dx| Coord(center.0 + dx, center.1 + dy))
+        })
+    }
+
+    /// Integer midpoint of `self` and `other`, rounding each component toward zero.
+    pub fn midpoint(&self, other: Coord) -> Coord {
+        // Widen first so the sum can't overflow.
+        let mid = |a: isize, b: isize| ((a as i128 + b as i128) / 2) as isize;
+        Self(mid(self.0, other.0), mid(self.1, other.1))
+    }
+
+    /// Smallest width in bytes (1, 2, 4 or 8) that holds both components.
+    pub fn min_bytes(&self) -> u8 {
+        let fits = |min: i64, max: i64| {
+            [self.0, self.1]
+                .iter()
+                .all(|&c| (min..=max).contains(&(c as i64)))
+        };
+        if fits(i8::MIN.into(), i8::MAX.into()) {
+            1
+        } else if fits(i16::MIN.into(), i16::MAX.into()) {
+            2
+        } else if fits(i32::MIN.into(), i32::MAX.into()) {
+            4
+        } else {
+            8
+        }
+    }
+
+    /// Writes a width tag byte (see `min_bytes`) followed by both components in little-endian.
+    pub fn write_packed<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        let width = self.min_bytes();
+        w.write_all(&[width])?;
+        for &c in &[self.0, self.1] {
+            w.write_all(&(c as i64).to_le_bytes()[..width as usize])?;
+        }
+        Ok(())
+    }
+
+    pub fn read_packed<R: Read>(r: &mut R) -> io::Result<Coord> {
+        let mut width = [0; 1];
+        r.read_exact(&mut width)?;
+        let width = width[0] as usize;
+        if ![1, 2, 4, 8].contains(&width) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "invalid coordinate width tag",
+            ));
+        }
+        let mut read_component = || -> io::Result<isize> {
+            let mut buf = [0; 8];
+            r.read_exact(&mut buf[..width])?;
+            // Sign-extend from the packed width.
+            let shift = 64 - 8 * width as u32;
+            let value = (i64::from_le_bytes(buf) << shift) >> shift;
+            isize::try_from(value)
+                .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "coordinate out of range"))
+        };
+        let x = read_component()?;
+        let y = read_component()?;
+        Ok(Self(x, y))
+    }
+
+    /// Divides both components by their gcd, giving the smallest integer step along `self`.
+    pub fn reduced_direction(&self) -> Coord {
+        let g = gcd(self.0.unsigned_abs(), self.1.unsigned_abs()) as i128;
+        if g == 0 {
+            return Self(0, 0);
+        }
+        Self((self.0 as i128 / g) as isize, (self.1 as i128 / g) as isize)
+    }
+
+    /// Rounds each component down (toward negative infinity) to a multiple of the grid size.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `grid_w` or `grid_h` is zero.
+    pub fn snap(&self, grid_w: usize, grid_h: usize) -> Coord {
+        assert!(grid_w != 0 && grid_h != 0, "grid size must be non-zero");
+        let (w, h) = (grid_w as isize, grid_h as isize);
+        Self(self.0.div_euclid(w) * w, self.1.div_euclid(h) * h)
+    }
+
+    /// Mirrors across the vertical line `x = axis_x`.
+    pub fn reflect_x(&self, axis_x: isize) -> Coord {
+        Self(2 * axis_x - self.0, self.1)
+    }
+
+    /// Mirrors across the horizontal line `y = axis_y`.
+    pub fn reflect_y(&self, axis_y: isize) -> Coord {
+        Self(self.0, 2 * axis_y - self.1)
+    }
+
+    pub fn cross(&self, other: Coord) -> isize {
+        self.0 * other.1 - self.1 * other.0
+    }
+
+    /// Sign of `(b - a).cross(c - a)`: `Greater` is a clockwise turn (with `y` pointing south),
+    /// `Less` counter-clockwise and `Equal` collinear.
+    pub fn orientation(a: Coord, b: Coord, c: Coord) -> Ordering {
+        let ab = Coord(b.0 - a.0, b.1 - a.1);
+        let ac = Coord(c.0 - a.0, c.1 - a.1);
+        ab.cross(ac).cmp(&0)
+    }
+
+    /// Rotates 90 degrees clockwise (with `y` pointing south) about `pivot`.
+    pub fn rotate_cw_around(&self, pivot: Coord) -> Coord {
+        let (dx, dy) = (self.0 - pivot.0, self.1 - pivot.1);
+        Self(pivot.0 - dy, pivot.1 + dx)
+    }
+
+    /// Rotates 90 degrees counter-clockwise (with `y` pointing south) about `pivot`.
+    pub fn rotate_ccw_around(&self, pivot: Coord) -> Coord {
+        let (dx, dy) = (self.0 - pivot.0, self.1 - pivot.1);
+        Self(pivot.0 + dy, pivot.1 - dx)
+    }
+
+    /// Classifies `self` as a direction vector into the nearest of the eight compass directions.
+    pub fn octant(&self) -> Option<Direction8> {
+        if self.0 == 0 && self.1 == 0 {
+            return None;
+        }
+        // A component is dropped when the vector lies within 22.5 degrees of the other axis,
+        // i.e. when `(|x| + |y|)^2 < 2 * other^2`, rearranged so it can't overflow.
+        let ax = self.0.unsigned_abs() as u128;
+        let ay = self.1.unsigned_abs() as u128;
+        let sx = if ax * (ax + 2 * ay) < ay * ay {
+            0
+        } else {
+            self.0.signum()
+        };
+        let sy = if ay * (ay + 2 * ax) < ax * ax {
+            0
+        } else {
+            self.1.signum()
+        };
+        Some(match (sx, sy) {
+            (0, -1) => Direction8::North,
+            (1, -1) => Direction8::NorthEast,
+            (1, 0) => Direction8::East,
+            (1, 1) => Direction8::SouthEast,
+            (0, 1) => Direction8::South,
+            (-1, 1) => Direction8::SouthWest,
+            (-1, 0) => Direction8::West,
+            (-1, -1) => Direction8::NorthWest,
+            _ => unreachable!(),
+        })
+    }
+
+    /// Every coordinate whose squared Euclidean distance from `self` is at most `radius^2`.
+    pub fn within_radius(&self, radius: usize) -> impl Iterator<Item = Coord> {
+        let center = *self;
+        let r = radius as isize;
+        (-r..=r).flat_map(move |dy| {
+            (-r..=r)
+                .filter(move |dx| dx * dx + dy * dy <= r * r)
+                .map(move |dx| Coord(center.0 + dx, center.1 + dy))
+        })
+    }
+
+    pub fn from_i16(x: i16, y: i16) -> Self {
+        Self(x.into(), y.into())
+    }
+
+    pub fn try_to_i16(&self) -> Option<(i16, i16)> {
+        Some((i16::try_from(self.0).ok()?, i16::try_from(self.1).ok()?))
+    }
+
+    pub fn try_to_i32(&self) -> Option<(i32, i32)> {
+        Some((i32::try_from(self.0).ok()?, i32::try_from(self.1).ok()?))
+    }
+
+    /// `self`, then each square ring out to `max_ring`. Every ring starts at its top-left corner
+    /// and runs clockwise (with `y` pointing south).
+    pub fn spiral(&self, max_ring: usize) -> impl Iterator<Item = Coord> {
+        let center = *self;
+        iter::once(center).chain((1..=max_ring as isize).flat_map(move |r| {
+            let side = 2 * r;
+            (0..4 * side).map(move |i| {
+                let (edge, t) = (i / side, i % side);
+                match edge {
+                    0 => Coord(center.0 - r + t, center.1 - r),
+                    1 => Coord(center.0 + r, center.1 - r + t),
+                    2 => Coord(center.0 + r - t, center.1 + r),
+                    _ => Coord(center.0 - r, center.1 + r - t),
+                }
+            })
+        }))
+    }
+
+    /// Leniently parses `"x,y"`, `"x y"` or `"(x, y)"`, ignoring surrounding whitespace.
+    pub fn parse_pair(s: &str) -> Option<Coord> {
+        let s = s.trim();
+        let s = match s.strip_prefix('(') {
+            Some(inner) => inner.strip_suffix(')')?,
+            None => s,
+        };
+        let (x, y) = match s.split_once(',') {
+            Some(pair) => pair,
+            None => {
+                let mut parts = s.split_whitespace();
+                let pair = (parts.next()?, parts.next()?);
+                if parts.next().is_some() {
+                    return None;
+                }
+                pair
+            }
+        };
+        Some(Self(x.trim().parse().ok()?, y.trim().parse().ok()?))
+    }
+
+    /// The `Direction` whose `offset` is exactly `self`.
+    pub fn to_direction(&self) -> Option<Direction> {
+        match (self.0, self.1) {
+            (0, -1) => Some(Direction::North),
+            (1, 0) => Some(Direction::East),
+            (0, 1) => Some(Direction::South),
+            (-1, 0) => Some(Direction::West),
+            _ => None,
+        }
+    }
+
+    /// Single grid step (possibly diagonal) from `self` toward `target`, or `(0, 0)` when equal.
+    pub fn toward(&self, target: Coord) -> Coord {
+        Self(
+            target.0.cmp(&self.0) as isize,
+            target.1.cmp(&self.1) as isize,
+        )
+    }
+
+    /// The orthogonal neighbours, in `Direction` order starting from north.
+    pub fn neighbors4(&self) -> [Coord; 4] {
+        [
+            Direction::North,
+            Direction::East,
+            Direction::South,
+            Direction::West,
+        ]
+        .map(|direction| *self + direction.offset())
+    }
+
+    /// All eight neighbours, clockwise starting from north.
+    pub fn neighbors8(&self) -> [Coord; 8] {
+        [
+            (0, -1),
+            (1, -1),
+            (1, 0),
+            (1, 1),
+            (0, 1),
+            (-1, 1),
+            (-1, 0),
+            (-1, -1),
+        ]
+        .map(|(dx, dy)| Self(self.0 + dx, self.1 + dy))
+    }
+
+    /// Component-wise `div_euclid`, rounding toward negative infinity for positive divisors,
+    /// unlike the truncating `Div` impl.
+    pub fn div_euclid_coord(&self, rhs: Coord) -> Coord {
+        Self(self.0.div_euclid(rhs.0), self.1.div_euclid(rhs.1))
+    }
+
+    /// Component-wise `rem_euclid`, which is never negative.
+    pub fn rem_euclid_coord(&self, rhs: Coord) -> Coord {
+        Self(self.0.rem_euclid(rhs.0), self.1.rem_euclid(rhs.1))
+    }
+
+    /// Splits a world coordinate into the chunk containing it and its offset in that chunk.
+    pub fn to_chunk_and_local(&self, chunk_size: Coord) -> (Coord, Coord) {
+        (
+            self.div_euclid_coord(chunk_size),
+            self.rem_euclid_coord(chunk_size),
+        )
+    }
+
+    /// Saturates at `usize::MAX`.
+    pub fn manhattan_distance(&self, other: Coord) -> usize {
+        self.0
+            .abs_diff(other.0)
+            .saturating_add(self.1.abs_diff(other.1))
+    }
+
+    pub fn chebyshev_distance(&self, other: Coord) -> usize {
+        self.0.abs_diff(other.0).max(self.1.abs_diff(other.1))
+    }
+
+    pub fn distance_squared(&self, other: Coord) -> u128 {
+        let (dx, dy) = (
+            self.0.abs_diff(other.0) as u128,
+            self.1.abs_diff(other.1) as u128,
+        );
+        dx * dx + dy * dy
+    }
+}
+
+impl fmt::Display for Coord {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "({}, {})", self.x(), self.y())
+    }
+}
+
+/// Axis-aligned box of coordinates with inclusive `min` and `max` corners.
+#[derive(PartialEq, Eq, Hash, Clone, Copy, Serialize, Deserialize, Debug)]
+pub struct Aabb {
+    pub min: Coord,
+    pub max: Coord,
+}
+
+impl Aabb {
+    /// The smallest box containing both corners, in any order.
+    pub fn new(a: Coord, b: Coord) -> Self {
+        Self {
+            min: Coord(a.0.min(b.0), a.1.min(b.1)),
+            max: Coord(a.0.max(b.0), a.1.max(b.1)),
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        (self.max.0 - self.min.0 + 1).max(0) as usize
+    }
+
+    pub fn height(&self) -> usize {
+        (self.max.1 - self.min.1 + 1).max(0) as usize
+    }
+
+    pub fn contains(&self, coord: Coord) -> bool {
+        (self.min.0..=self.max.0).contains(&coord.0) && (self.min.1..=self.max.1).contains(&coord.1)
+    }
+
+    pub fn intersects(&self, other: &Aabb) -> bool {
+        self.intersection(other).is_some()
+    }
+
+    pub fn intersection(&self, other: &Aabb) -> Option<Aabb> {
+        let min = Coord(self.min.0.max(other.min.0), self.min.1.max(other.min.1));
+        let max = Coord(self.max.0.min(other.max.0), self.max.1.min(other.max.1));
+        if min.0 > max.0 || min.1 > max.1 {
+            return None;
+        }
+        Some(Aabb { min, max })
+    }
+
+    pub fn union(&self, other: &Aabb) -> Aabb {
+        Aabb {
+            min: Coord(self.min.0.min(other.min.0), self.min.1.min(other.min.1)),
+            max: Coord(self.max.0.max(other.max.0), self.max.1.max(other.max.1)),
+        }
+    }
+
+    /// Every coordinate in the box in row-major order.
+    pub fn iter(&self) -> impl Iterator<Item = Coord> {
+        let Aabb { min, max } = *self;
+        (min.1..=max.1).flat_map(move |y| (min.0..=max.0).map(move |x| Coord(x, y)))
+    }
+}
+
+/// Name used by world logic for an inclusive rectangle of coordinates.
+pub type CoordRect = Aabb;
+
+/// Serde `with` helper for `HashMap<Coord, V>` fields that writes keys as `"x,y"` strings, as
+/// required by formats such as JSON.
+pub mod coord_key {
+    use std::collections::HashMap;
+
+    use serde::{de::Error, Deserialize, Deserializer, Serialize, Serializer};
+
+    use crate::Coord;
+
+    pub fn serialize<S: Serializer, V: Serialize>(
+        map: &HashMap<Coord, V>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.collect_map(map.iter().map(|(k, v)| (format!("{},{}", k.0, k.1), v)))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>, V: Deserialize<'de>>(
+        deserializer: D,
+    ) -> Result<HashMap<Coord, V>, D::Error> {
+        HashMap::<String, V>::deserialize(deserializer)?
+            .into_iter()
+            .map(|(key, value)| {
+                let coord = key
+                    .split_once(',')
+                    .and_then(|(x, y)| Some(Coord(x.parse().ok()?, y.parse().ok()?)))
+                    .ok_or_else(|| D::Error::custom(format!("invalid coordinate key {:?}", key)))?;
+                Ok((coord, value))
+            })
+            .collect()
+    }
+}
+
+/// Integer affine transform: `apply(c) = m * c + t`.
+#[derive(PartialEq, Eq, Hash, Clone, Copy, Serialize, Deserialize, Debug)]
+pub struct Affine2 {
+    pub m: [[isize; 2]; 2],
+    pub t: Coord,
+}
+
+impl Affine2 {
+    pub fn identity() -> Self {
+        Self {
+            m: [[1, 0], [0, 1]],
+            t: Coord(0, 0),
+        }
+    }
+
+    pub fn translation(t: Coord) -> Self {
+        Self {
+            t,
+            ..Self::identity()
+        }
+    }
+
+    /// Rotation by `quarter_turns` of 90 degrees clockwise (with `y` pointing south); negative
+    /// values turn counter-clockwise.
+    pub fn rotation(quarter_turns: isize) -> Self {
+        let m = match quarter_turns.rem_euclid(4) {
+            0 => [[1, 0], [0, 1]],
+            1 => [[0, -1], [1, 0]],
+            2 => [[-1, 0], [0, -1]],
+            _ => [[0, 1], [-1, 0]],
+        };
+        Self { m, t: Coord(0, 0) }
+    }
+
+    pub fn apply(&self, c: Coord) -> Coord {
+        Coord(
+            self.m[0][0] * c.0 + self.m[0][1] * c.1 + self.t.0,
+            self.m[1][0] * c.0 + self.m[1][1] * c.1 + self.t.1,
+        )
+    }
+
+    /// The transform applying `other` first, then `self`.
+    pub fn compose(&self, other: &Affine2) -> Affine2 {
+        let (a, b) = (&self.m, &other.m);
+        let m = [
+            [
+                a[0][0] * b[0][0] + a[0][1] * b[1][0],
+                a[0][0] * b[0][1] + a[0][1] * b[1][1],
+            ],
+            [
+                a[1][0] * b[0][0] + a[1][1] * b[1][0],
+                a[1][0] * b[0][1] + a[1][1] * b[1][1],
+            ],
+        ];
+        Affine2 {
+            m,
+            t: self.apply(other.t),
+        }
+    }
+}
+
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub struct CoordParseError;
+
+impl fmt::Display for CoordParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "expected a coordinate of the form (x, y)")
+    }
+}
+
+impl error::Error for CoordParseError {}
+
+impl FromStr for Coord {
+    type Err = CoordParseError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (x, y) = s
+            .trim()
+            .strip_prefix('(')
+            .and_then(|s| s.strip_suffix(')'))
+            .and_then(|s| s.split_once(','))
+            .ok_or(CoordParseError)?;
+        let x = x.trim().parse().map_err(|_| CoordParseError)?;
+        let y = y.trim().parse().map_err(|_| CoordParseError)?;
+        Ok(Self(x, y))
+    }
+}
+
+impl Add<Coord> for Coord {
+    type Output = Self;
+    fn add(self, rhs: Coord) -> Self::Output {
+        Self(self.0 + rhs.0, self.1 + rhs.1)
+    }
+}
+
+impl Mul<Coord> for Coord {
+    type Output = Self;
+    fn mul(self, rhs: Coord) -> Self::Output {
+        Self(self.0 * rhs.0, self.1 * rhs.1)
+    }
+}
+
+impl Div<Coord> for Coord {
+    type Output = Self;
+    fn div(self, rhs: Coord) -> Self::Output {
+        Self(self.0 / rhs.0, self.1 / rhs.1)
+    }
+}
+
+impl Div<isize> for Coord {
+    type Output = Self;
+    fn div(self, rhs: isize) -> Self::Output {
+        Self(self.0 / rhs, self.1 / rhs)
+    }
+}
+
+impl Rem<Coord> for Coord {
+    type Output = Self;
+    fn rem(self, rhs: Self) -> Self::Output {
+        Self(self.0 % rhs.0, self.1 % rhs.1)
+    }
+}
+
+const WORLD_MAGIC: &[u8; 4] = b"VWLD";
+
+/// Chunks keyed by chunk coordinate, addressed in world space.
+///
+/// World coordinates map to chunks with floor division, so `(-1, -1)` lives in chunk `(-1, -1)`
+/// at local offset `(X - 1, Y - 1)`.
+///
+/// Every chunk handed out mutably or written through the world is marked dirty until the next
+/// `take_dirty`, and removing a chunk clears its flag. The dirty set is neither serialized nor
+/// compared by `==`.
+///
+/// Chunks are reference counted, so clones of a world and chunks merged by `compact` share
+/// storage until one of them is written, at which point it gets its own copy.
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct World<const X: usize, const Y: usize> {
+    chunks: HashMap<Coord, Arc<ChunkData<X, Y>>>,
+    #[serde(skip)]
+    dirty: HashSet<Coord>,
+}
+
+impl<const X: usize, const Y: usize> PartialEq for World<X, Y> {
+    fn eq(&self, other: &Self) -> bool {
+        self.chunks == other.chunks
+    }
+}
+
+impl<const X: usize, const Y: usize> World<X, Y> {
+    pub fn new() -> Self {
+        Self {
+            chunks: HashMap::new(),
+            dirty: HashSet::new(),
+        }
+    }
+
+    /// Coordinate of the chunk containing `world`.
+    pub fn chunk_at(world: Coord) -> Coord {
+        world.div_euclid_coord(Coord(X as isize, Y as isize))
+    }
+
+    /// Position of `world` within its chunk.
+    pub fn local_offset(world: Coord) -> Coord {
+        world.rem_euclid_coord(Coord(X as isize, Y as isize))
+    }
+
+    /// World coordinate of `local` within the chunk at `chunk`.
+    pub fn to_world(chunk: Coord, local: Coord) -> Coord {
+        Coord(
+            chunk.0 * X as isize + local.0,
+            chunk.1 * Y as isize + local.1,
+        )
+    }
+
+    pub fn len(&self) -> usize {
+        self.chunks.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.chunks.is_empty()
+    }
+
+    pub fn get_chunk(&self, chunk: Coord) -> Option<&ChunkData<X, Y>> {
+        self.chunks.get(&chunk).map(|data| &**data)
+    }
+
+    /// The chunks around the chunk at `chunk`, in `Coord::neighbors8` order: clockwise from
+    /// north, so index 0 is north and index 2 east. Unloaded neighbours are `None`.
+    pub fn neighbors_8(&self, chunk: Coord) -> [Option<&ChunkData<X, Y>>; 8] {
+        chunk.neighbors8().map(|coord| self.get_chunk(coord))
+    }
+
+    /// The chunk containing `world` and the position of `world` within it.
+    pub fn chunk_at_world(&self, world: Coord) -> Option<(&ChunkData<X, Y>, Coord)> {
+        let chunk = self.get_chunk(Self::chunk_at(world))?;
+        Some((chunk, Self::local_offset(world)))
+    }
+
+    pub fn get_chunk_mut(&mut self, chunk: Coord) -> Option<&mut ChunkData<X, Y>> {
+        let data = self.chunks.get_mut(&chunk)?;
+        self.dirty.insert(chunk);
+        Some(Arc::make_mut(data))
+    }
+
+    /// The slot for the chunk at `chunk`, for in-place insert-or-update like `HashMap::entry`.
+    pub fn chunk_entry(&mut self, chunk: Coord) -> ChunkEntry<'_, X, Y> {
+        ChunkEntry {
+            entry: self.chunks.entry(chunk),
+            dirty: &mut self.dirty,
+        }
+    }
+
+    pub fn insert_chunk(&mut self, chunk: Coord, data: ChunkData<X, Y>) -> Option<ChunkData<X, Y>> {
+        self.dirty.insert(chunk);
+        self.chunks
+            .insert(chunk, Arc::new(data))
+            .map(Arc::unwrap_or_clone)
+    }
+
+    pub fn remove_chunk(&mut self, chunk: Coord) -> Option<ChunkData<X, Y>> {
+        self.dirty.remove(&chunk);
+        self.chunks.remove(&chunk).map(Arc::unwrap_or_clone)
+    }
+
+    /// Generates every chunk in the inclusive rectangle spanned by the two corners that isn't
+    /// loaded yet, passing `gen` its chunk coordinate. Loaded chunks are left untouched.
+    pub fn generate_region<F: FnMut(Coord) -> ChunkData<X, Y>>(
+        &mut self,
+        min_chunk: Coord,
+        max_chunk: Coord,
+        mut gen: F,
+    ) {
+        for coord in Aabb::new(min_chunk, max_chunk).iter() {
+            let dirty = &mut self.dirty;
+            self.chunks.entry(coord).or_insert_with(|| {
+                dirty.insert(coord);
+                Arc::new(gen(coord))
+            });
+        }
+    }
+
+    /// Generates each chunk in `coords` that isn't loaded yet, marking it dirty, and returns
+    /// the newly created coordinates in the order they were first listed.
+    pub fn ensure_loaded<F: FnMut(Coord) -> ChunkData<X, Y>>(
+        &mut self,
+        coords: &[Coord],
+        mut gen: F,
+    ) -> Vec<Coord> {
+        let mut created = Vec::new();
+        for &coord in coords {
+            if let hash_map::Entry::Vacant(slot) = self.chunks.entry(coord) {
+                slot.insert(Arc::new(gen(coord)));
+                self.dirty.insert(coord);
+                created.push(coord);
+            }
+        }
+        created
+    }
+
+    /// Removes every chunk made up entirely of the default block, returning how many went.
+    pub fn trim(&mut self) -> usize {
+        let before = self.chunks.len();
+        self.retain_chunks(|_, chunk| chunk.is_uniform() != Some(BlockID::default()));
+        before - self.chunks.len()
+    }
+
+    /// Keeps only the chunks for which `f` returns `true`, clearing the dirty flags of the rest.
+    pub fn retain_chunks<F: FnMut(Coord, &ChunkData<X, Y>) -> bool>(&mut self, mut f: F) {
+        let dirty = &mut self.dirty;
+        self.chunks.retain(|&coord, chunk| {
+            let keep = f(coord, chunk);
+            if !keep {
+                dirty.remove(&coord);
+            }
+            keep
+        });
+    }
+
+    /// Moves every chunk from `coord` to `coord + delta`, keeping its contents, and marks them
+    /// all dirty at their new coordinates.
+    ///
+    /// A translation maps distinct chunks to distinct coordinates, so chunks never collide.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a shifted chunk coordinate overflows, before any chunk has moved.
+    pub fn shift_origin(&mut self, delta: Coord) {
+        let shift = |c: Coord| Some(Coord(c.0.checked_add(delta.0)?, c.1.checked_add(delta.1)?));
+        assert!(
+            self.chunks.keys().all(|&c| shift(c).is_some()),
+            "shifting the world origin overflowed a chunk coordinate"
+        );
+        self.chunks = self
+            .chunks
+            .drain()
+            .map(|(coord, chunk)| (shift(coord).unwrap(), chunk))
+            .collect();
+        self.dirty = self.chunks.keys().copied().collect();
+    }
+
+    /// Replaces every block that has an entry in `mapping`, leaving other ids alone. Only the
+    /// chunks where a cell actually changes are written and marked dirty.
+    pub fn remap_blocks(&mut self, mapping: &HashMap<BlockID, BlockID>) {
+        let remapped = |block: &BlockID| mapping.get(block).filter(|&to| to != block);
+        for (&coord, chunk) in &mut self.chunks {
+            if chunk.iter().all(|(_, block)| remapped(block).is_none()) {
+                continue;
+            }
+            for (_, cell) in Arc::make_mut(chunk).iter_mut() {
+                if let Some(&to) = remapped(cell) {
+                    *cell = to;
+                }
+            }
+            self.dirty.insert(coord);
+        }
+    }
+
+    /// Makes equal chunks share one allocation, returning how many chunks gave up their own
+    /// copy. Contents and dirty flags are unchanged.
+    pub fn compact(&mut self) -> usize {
+        let mut distinct: HashSet<Arc<ChunkData<X, Y>>> = HashSet::new();
+        let mut merged = 0;
+        for chunk in self.chunks.values_mut() {
+            match distinct.get(&**chunk) {
+                Some(shared) if Arc::ptr_eq(shared, chunk) => {}
+                Some(shared) => {
+                    *chunk = Arc::clone(shared);
+                    merged += 1;
+                }
+                None => {
+                    distinct.insert(Arc::clone(chunk));
+                }
+            }
+        }
+        merged
+    }
+
+    /// Loaded chunks with their chunk coordinates, in unspecified order.
+    pub fn chunks(&self) -> impl Iterator<Item = (Coord, &ChunkData<X, Y>)> {
+        self.chunks.iter().map(|(&coord, chunk)| (coord, &**chunk))
+    }
+
+    /// Marks every loaded chunk dirty.
+    pub fn chunks_mut(&mut self) -> impl Iterator<Item = (Coord, &mut ChunkData<X, Y>)> {
+        self.dirty.extend(self.chunks.keys().copied());
+        self.chunks
+            .iter_mut()
+            .map(|(&coord, chunk)| (coord, Arc::make_mut(chunk)))
+    }
+
+    /// Loaded chunks whose coordinates lie in the inclusive rectangle spanned by the two
+    /// corners, in unspecified order.
+    pub fn iter_region_chunks(
+        &self,
+        min_chunk: Coord,
+        max_chunk: Coord,
+    ) -> impl Iterator<Item = (Coord, &ChunkData<X, Y>)> {
+        let region = Aabb::new(min_chunk, max_chunk);
+        self.chunks()
+            .filter(move |&(coord, _)| region.contains(coord))
+    }
+
+    /// Every block of every loaded chunk with its world coordinate. Chunks come in unspecified
+    /// order, the cells of each chunk in row-major order.
+    pub fn iter_blocks(&self) -> impl Iterator<Item = (Coord, BlockID)> + '_ {
+        self.chunks().flat_map(|(coord, chunk)| {
+            chunk
+                .enumerate_cells()
+                .map(move |(local, block)| (Self::to_world(coord, local), block))
+        })
+    }
+
+    /// How many `id` blocks each loaded chunk holds. Chunks without any are left out.
+    pub fn block_counts_by_chunk(&self, id: BlockID) -> HashMap<Coord, usize> {
+        self.chunks()
+            .map(|(coord, chunk)| (coord, chunk.iter().filter(|&(_, &b)| b == id).count()))
+            .filter(|&(_, count)| count > 0)
+            .collect()
+    }
+
+    /// Calls `f` with every loaded chunk, in unspecified order.
+    pub fn for_each_chunk<F: FnMut(Coord, &ChunkData<X, Y>)>(&self, mut f: F) {
+        for (coord, chunk) in self.chunks() {
+            f(coord, chunk);
+        }
+    }
+
+    /// Like `for_each_chunk`, but with mutable access. Every visited chunk is marked dirty,
+    /// whether or not `f` changes it; use `get_chunk_mut` to dirty only the chunks that change.
+    pub fn for_each_chunk_mut<F: FnMut(Coord, &mut ChunkData<X, Y>)>(&mut self, mut f: F) {
+        for (coord, chunk) in self.chunks_mut() {
+            f(coord, chunk);
+        }
+    }
+
+    /// The block at `world`, or `None` if its chunk isn't loaded.
+    pub fn get_block(&self, world: Coord) -> Option<BlockID> {
+        let local = Self::local_offset(world);
+        self.get_chunk(Self::chunk_at(world))
+            .map(|chunk| chunk[local.1 as usize][local.0 as usize])
+    }
+
+    /// The block at `world`, or `default` if its chunk isn't loaded.
+    pub fn get_block_or(&self, world: Coord, default: BlockID) -> BlockID {
+        self.get_block(world).unwrap_or(default)
+    }
+
+    /// The block at `world` and the one a step away in `dir`, each `None` if its chunk isn't
+    /// loaded. The neighbour may lie in an adjacent chunk.
+    pub fn block_and_neighbor(
+        &self,
+        world: Coord,
+        dir: Direction,
+    ) -> (Option<BlockID>, Option<BlockID>) {
+        (self.get_block(world), self.get_block(world + dir.offset()))
+    }
+
+    /// The closest `id` block to `from` by Chebyshev distance, searching the square rings of
+    /// `Coord::spiral` out to `max_radius`. Ties within a ring go to the first cell in spiral
+    /// order. Unloaded cells never match.
+    pub fn nearest_block(&self, from: Coord, id: BlockID, max_radius: usize) -> Option<Coord> {
+        from.spiral(max_radius)
+            .find(|&coord| self.get_block(coord) == Some(id))
+    }
+
+    /// The orthogonal neighbours of `at` whose blocks pass `is_walkable`, in `Coord::neighbors4`
+    /// order. Cells in unloaded chunks count as blocked.
+    pub fn walkable_neighbors(
+        &self,
+        at: Coord,
+        is_walkable: impl Fn(BlockID) -> bool,
+    ) -> Vec<Coord> {
+        IntoIterator::into_iter(at.neighbors4())
+            .filter(|&coord| self.get_block(coord).is_some_and(&is_walkable))
+            .collect()
+    }
+
+    /// Sets the block at `world`, returning the previous block, or `None` without writing if its
+    /// chunk isn't loaded.
+    pub fn set_block(&mut self, world: Coord, block: BlockID) -> Option<BlockID> {
+        let local = Self::local_offset(world);
+        let cell =
+            &mut self.get_chunk_mut(Self::chunk_at(world))?[local.1 as usize][local.0 as usize];
+        Some(std::mem::replace(cell, block))
+    }
+
+    /// Like `set_block`, but first loads a missing chunk from `default_chunk`, which is given
+    /// the chunk coordinate. Returns the previous block.
+    pub fn set_block_autoload<F: Fn(Coord) -> ChunkData<X, Y>>(
+        &mut self,
+        world: Coord,
+        block: BlockID,
+        default_chunk: F,
+    ) -> BlockID {
+        let (chunk, local) = (Self::chunk_at(world), Self::local_offset(world));
+        self.dirty.insert(chunk);
+        let data = self
+            .chunks
+            .entry(chunk)
+            .or_insert_with(|| Arc::new(default_chunk(chunk)));
+        std::mem::replace(
+            &mut Arc::make_mut(data)[local.1 as usize][local.0 as usize],
+            block,
+        )
+    }
+
+    /// Copies the blocks in the inclusive rectangle spanned by `src_min` and `src_max` so that
+    /// the source's top-left corner lands on `dst_min`. The source is read in full before
+    /// anything is written, so overlapping regions copy correctly.
+    ///
+    /// Cells in unloaded source chunks are skipped, and like `set_block` no chunk is loaded to
+    /// receive a write: cells landing in unloaded destination chunks are dropped.
+    pub fn copy_region(&mut self, src_min: Coord, src_max: Coord, dst_min: Coord) {
+        let region = Aabb::new(src_min, src_max);
+        let blocks: Vec<_> = region
+            .iter()
+            .filter_map(|c| {
+                let block = self.get_block(c)?;
+                Some((Coord(c.0 - region.min.0, c.1 - region.min.1), block))
+            })
+            .collect();
+        for (offset, block) in blocks {
+            self.set_block(dst_min + offset, block);
+        }
+    }
+
+    /// Writes the magic `VWLD`, the chunk count as a little-endian `u64`, then each chunk's
+    /// `Coord::write_packed` coordinate followed by its `ChunkData::write_to` payload, in
+    /// ascending `(y, x)` chunk order.
+    pub fn save_to<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        let mut coords: Vec<_> = self.chunks.keys().copied().collect();
+        coords.sort_by_key(|c| (c.1, c.0));
+        w.write_all(WORLD_MAGIC)?;
+        w.write_all(&(coords.len() as u64).to_le_bytes())?;
+        for coord in coords {
+            coord.write_packed(w)?;
+            self.chunks[&coord].write_to(w)?;
+        }
+        Ok(())
+    }
+
+    /// Reads the `save_to` format. A stream that ends early fails with `UnexpectedEof`, and the
+    /// loaded chunks start out clean.
+    pub fn load_from<R: Read>(r: &mut R) -> io::Result<Self> {
+        let mut magic = [0; 4];
+        r.read_exact(&mut magic)?;
+        if &magic != WORLD_MAGIC {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "not a world file",
+            ));
+        }
+        let mut count = [0; 8];
+        r.read_exact(&mut count)?;
+        let count = u64::from_le_bytes(count);
+        let mut world = Self::new();
+        for _ in 0..count {
+            let coord = Coord::read_packed(r)?;
+            let chunk = ChunkData::read_streaming(r)?;
+            if world.chunks.insert(coord, Arc::new(chunk)).is_some() {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("duplicate chunk {}", coord),
+                ));
+            }
+        }
+        Ok(world)
+    }
+
+    pub fn dirty_len(&self) -> usize {
+        self.dirty.len()
+    }
+
+    pub fn is_dirty(&self, chunk: Coord) -> bool {
+        self.dirty.contains(&chunk)
+    }
+
+    /// Clears the dirty set, returning the chunks it held in unspecified order.
+    pub fn take_dirty(&mut self) -> impl Iterator<Item = Coord> {
+        std::mem::take(&mut self.dirty).into_iter()
+    }
+}
+
+/// A chunk slot in a `World`, from `World::chunk_entry`. Every method that hands out or changes
+/// the chunk marks it dirty.
+pub struct ChunkEntry<'a, const X: usize, const Y: usize> {
+    entry: hash_map::Entry<'a, Coord, Arc<ChunkData<X, Y>>>,
+    dirty: &'a mut HashSet<Coord>,
+}
+
+impl<'a, const X: usize, const Y: usize> ChunkEntry<'a, X, Y> {
+    pub fn key(&self) -> Coord {
+        *self.entry.key()
+    }
+
+    pub fn or_insert(self, default: ChunkData<X, Y>) -> &'a mut ChunkData<X, Y> {
+        self.or_insert_with(|| default)
+    }
+
+    pub fn or_insert_with<F: FnOnce() -> ChunkData<X, Y>>(self, f: F) -> &'a mut ChunkData<X, Y> {
+        self.dirty.insert(*self.entry.key());
+        Arc::make_mut(self.entry.or_insert_with(|| Arc::new(f())))
+    }
+
+    /// Runs `f` on the chunk if it is loaded.
+    pub fn and_modify<F: FnOnce(&mut ChunkData<X, Y>)>(self, f: F) -> Self {
+        let Self { entry, dirty } = self;
+        let key = *entry.key();
+        let entry = entry.and_modify(|chunk| {
+            dirty.insert(key);
+            f(Arc::make_mut(chunk))
+        });
+        Self { entry, dirty }
+    }
+}
+
+fn gcd(mut a: usize, mut b: usize) -> usize {
+    while b != 0 {
+        let r = a % b;
+        a = b;
+        b = r;
+    }
+    a
+}
+
+fn fnv1a<'a>(blocks: impl Iterator<Item = &'a BlockID>) -> u64 {
+    const OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+    const PRIME: u64 = 0x0000_0100_0000_01b3;
+    blocks
+        .flat_map(|&block| (block as u64).to_le_bytes())
+        .fold(OFFSET_BASIS, |hash, byte| {
+            (hash ^ byte as u64).wrapping_mul(PRIME)
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn world_dirty_set_is_queried_without_draining() {
+        let mut world = World::<4, 4>::new();
+        world.insert_chunk(Coord(0, 0), ChunkData::default());
+        world.insert_chunk(Coord(1, 0), ChunkData::default());
+        world.take_dirty().for_each(drop);
+        assert_eq!(world.dirty_len(), 0);
+
+        world.set_block(Coord(1, 1), 3);
+        world.set_block(Coord(2, 2), 4);
+        world.set_block(Coord(-1, 0), 5);
+        world.insert_chunk(Coord(0, 1), ChunkData::default());
+        assert_eq!(world.dirty_len(), 2);
+        assert!(world.is_dirty(Coord(0, 0)));
+        assert!(world.is_dirty(Coord(0, 1)));
+        assert!(!world.is_dirty(Coord(1, 0)));
+        assert!(!world.is_dirty(Coord(-1, 0)));
+
+        let mut taken: Vec<_> = world.take_dirty().collect();
+        taken.sort_by_key(|c| (c.1, c.0));
+        assert_eq!(taken, vec![Coord(0, 0), Coord(0, 1)]);
+        assert_eq!(world.dirty_len(), 0);
+        assert!(!world.is_dirty(Coord(0, 0)));
+    }
+
+    #[test]
+    fn removing_a_chunk_clears_its_dirty_flag() {
+        let mut world = World::<4, 4>::new();
+        world.insert_chunk(Coord(0, 0), ChunkData::default());
+        world.remove_chunk(Coord(0, 0));
+        assert!(!world.is_dirty(Coord(0, 0)));
+        assert_eq!(world.dirty_len(), 0);
+    }
+
+    #[test]
+    fn as_array_writes_are_visible_through_indexing() {
+        let mut chunk = ChunkData::<3, 2>::default();
+        for (y, row) in chunk.as_array_mut().iter_mut().enumerate() {
+            for (x, cell) in row.iter_mut().enumerate() {
+                *cell = 10 * y + x;
+            }
+        }
+        for y in 0..2 {
+            for x in 0..3 {
+                assert_eq!(chunk[y][x], 10 * y + x);
+            }
+        }
+        assert_eq!(chunk.as_array(), &[[0, 1, 2], [10, 11, 12]]);
+    }
+
+    #[test]
+    fn octant_of_pure_directions() {
+        let cases = [
+            ((0, -1), Direction8::North),
+            ((3, -3), Direction8::NorthEast),
+            ((2, 0), Direction8::East),
+            ((1, 1), Direction8::SouthEast),
+            ((0, 7), Direction8::South),
+            ((-4, 4), Direction8::SouthWest),
+            ((-1, 0), Direction8::West),
+            ((-2, -2), Direction8::NorthWest),
+        ];
+        for &((x, y), direction) in &cases {
+            assert_eq!(Coord(x, y).octant(), Some(direction), "({}, {})", x, y);
+        }
+    }
+
+    #[test]
+    fn octant_rounds_to_the_nearest_direction() {
+        assert_eq!(Coord(5, 1).octant(), Some(Direction8::East));
+        assert_eq!(Coord(5, 3).octant(), Some(Direction8::SouthEast));
+        assert_eq!(Coord(-1, -5).octant(), Some(Direction8::North));
+        assert_eq!(Coord(-3, 5).octant(), Some(Direction8::SouthWest));
+        assert_eq!(Coord(0, 0).octant(), None);
+    }
+
+    #[test]
+    fn checksum_matches_for_equal_chunks_and_changes_on_set() {
+        let mut a = ChunkData::<4, 4>::new(2);
+        let b = ChunkData::<4, 4>::new(2);
+        assert_eq!(a.checksum(), b.checksum());
+        a.set(3, 1, 7).unwrap();
+        assert_ne!(a.checksum(), b.checksum());
+    }
+
+    #[test]
+    fn for_each_chunk_visits_every_loaded_chunk_once() {
+        let mut world = World::<2, 2>::new();
+        let coords = [Coord(0, 0), Coord(-1, 3), Coord(5, -2)];
+        for &coord in &coords {
+            world.insert_chunk(coord, ChunkData::default());
+        }
+        let mut seen = Vec::new();
+        world.for_each_chunk(|coord, _| seen.push(coord));
+        seen.sort_by_key(|c| (c.1, c.0));
+        assert_eq!(seen, vec![Coord(5, -2), Coord(0, 0), Coord(-1, 3)]);
+    }
+
+    #[test]
+    fn for_each_chunk_mut_marks_visited_chunks_dirty() {
+        let mut world = World::<2, 2>::new();
+        world.insert_chunk(Coord(0, 0), ChunkData::default());
+        world.insert_chunk(Coord(1, 0), ChunkData::default());
+        world.take_dirty().for_each(drop);
+
+        let mut calls = 0;
+        world.for_each_chunk_mut(|coord, chunk| {
+            calls += 1;
+            chunk.fill(coord.0 as BlockID + 1);
+        });
+        assert_eq!(calls, 2);
+        assert_eq!(world.get_block(Coord(3, 1)), Some(2));
+        assert!(world.is_dirty(Coord(0, 0)));
+        assert!(world.is_dirty(Coord(1, 0)));
+    }
+
+    #[test]
+    fn rotation_around_a_pivot() {
+        let pivot = Coord(2, 3);
+        assert_eq!(Coord(4, 3).rotate_cw_around(pivot), Coord(2, 5));
+        assert_eq!(Coord(4, 3).rotate_ccw_around(pivot), Coord(2, 1));
+        assert_eq!(Coord(3, 1).rotate_cw_around(pivot), Coord(4, 4));
+        assert_eq!(pivot.rotate_cw_around(pivot), pivot);
+    }
+
+    #[test]
+    fn four_rotations_are_the_identity() {
+        let pivot = Coord(-3, 7);
+        for &c in &[Coord(0, 0), Coord(5, -2), Coord(-3, 9)] {
+            let cw = (0..4).fold(c, |c, _| c.rotate_cw_around(pivot));
+            let ccw = (0..4).fold(c, |c, _| c.rotate_ccw_around(pivot));
+            assert_eq!(cw, c);
+            assert_eq!(ccw, c);
+            assert_eq!(c.rotate_cw_around(pivot).rotate_ccw_around(pivot), c);
+        }
+    }
+
+    #[test]
+    fn apply_mask_on_the_diagonal() {
+        let mut chunk = ChunkData::<3, 3>::default();
+        chunk[1][1] = 5;
+        let mut mask = [[false; 3]; 3];
+        for (i, row) in mask.iter_mut().enumerate() {
+            row[i] = true;
+        }
+        assert_eq!(chunk.apply_mask(&mask, 5), 2);
+        assert_eq!(chunk.as_array(), &[[5, 0, 0], [0, 5, 0], [0, 0, 5]]);
+        assert_eq!(chunk.apply_mask(&mask, 5), 0);
+    }
+
+    #[test]
+    fn map_applies_to_both_components() {
+        assert_eq!(Coord(3, -4).map(|c| c * 2), Coord(6, -8));
+        assert_eq!(Coord(-12, 40).map(|c| c.clamp(-10, 10)), Coord(-10, 10));
+        assert_eq!(Coord(5, -5).map(|c| c.clamp(-10, 10)), Coord(5, -5));
+    }
+
+    #[test]
+    fn get_block_or_falls_back_for_unloaded_chunks() {
+        let mut world = World::<4, 4>::new();
+        world.insert_chunk(Coord(0, 0), ChunkData::new(3));
+        assert_eq!(world.get_block_or(Coord(2, 1), 9), 3);
+        assert_eq!(world.get_block_or(Coord(-1, 1), 9), 9);
+        assert_eq!(world.get_block_or(Coord(4, 0), 9), 9);
+    }
+
+    #[test]
+    fn fill_makes_a_chunk_uniform() {
+        let mut chunk = ChunkData::<3, 2>::default();
+        chunk[0][1] = 4;
+        chunk[1][2] = 9;
+        chunk.fill(6);
+        assert!(chunk.iter().all(|(_, &block)| block == 6));
+
+        let mut row = chunk[1];
+        row[0] = 1;
+        row.fill(2);
+        assert_eq!(row, ChunkDataRow::new(2));
+    }
+
+    #[test]
+    fn within_manhattan_counts_and_bounds() {
+        let center = Coord(-3, 5);
+        for &r in &[0, 1, 2, 5] {
+            let cells: Vec<_> = center.within_manhattan(r).collect();
+            assert_eq!(cells.len(), 2 * r * r + 2 * r + 1, "radius {}", r);
+            assert!(cells.iter().all(|c| c.manhattan_distance(center) <= r));
+            assert!(cells.contains(&center));
+            let unique: HashSet<_> = cells.iter().collect();
+            assert_eq!(unique.len(), cells.len());
+        }
+    }
+
+    #[test]
+    fn content_bounds_of_empty_chunk_is_none() {
+        assert_eq!(ChunkData::<4, 4>::default().content_bounds(), None);
+    }
+
+    #[test]
+    fn content_bounds_of_single_cell() {
+        let mut chunk = ChunkData::<4, 4>::default();
+        chunk[2][1] = 5;
+        assert_eq!(
+            chunk.content_bounds(),
+            Some(Aabb::new(Coord(1, 2), Coord(1, 2)))
+        );
+    }
+
+    #[test]
+    fn content_bounds_of_scattered_cells() {
+        let mut chunk = ChunkData::<5, 5>::default();
+        chunk[1][3] = 1;
+        chunk[3][0] = 2;
+        chunk[4][2] = 3;
+        assert_eq!(
+            chunk.content_bounds(),
+            Some(Aabb::new(Coord(0, 1), Coord(3, 4)))
+        );
+    }
+
+    crate::deserializable! {
+        #[derive(PartialEq, Debug)]
+        struct Marker {
+            ids: [BlockID; 3],
+            pub origin: [Coord; 1],
+        }
+    }
+
+    #[test]
+    fn deserializable_macro_round_trip() {
+        let marker = Marker {
+            ids: [4, 0, 9],
+            origin: [Coord(-2, 7)],
+        };
+        let value = DeSerializable::serialize(&marker, test_value::ValueSerializer).unwrap();
+        let back: Marker = DeSerializable::deserialize(value).unwrap();
+        assert_eq!(back, marker);
+    }
+
+    #[test]
+    fn deserializable_macro_rejects_wrong_field_counts() {
+        use test_value::Value;
+        let ids = Value::Seq(vec![Value::U64(1), Value::U64(2), Value::U64(3)]);
+        let origin = test_value::to_value(&[Coord(0, 0)][..]).unwrap();
+        let short = Value::Seq(vec![ids.clone()]);
+        let long = Value::Seq(vec![ids, origin.clone(), origin]);
+        assert!(<Marker as DeSerializable>::deserialize(short).is_err());
+        assert!(<Marker as DeSerializable>::deserialize(long).is_err());
+    }
+
+    #[test]
+    fn is_uniform_reports_the_shared_value() {
+        let mut chunk = ChunkData::<2, 2>::new(3);
+        assert_eq!(chunk.is_uniform(), Some(3));
+        chunk[1][0] = 4;
+        assert_eq!(chunk.is_uniform(), None);
+    }
+
+    #[test]
+    fn trim_removes_only_empty_chunks() {
+        let mut world = World::<2, 2>::new();
+        let mut partly_empty = ChunkData::default();
+        partly_empty[1][1] = 5;
+        world.insert_chunk(Coord(0, 0), ChunkData::default());
+        world.insert_chunk(Coord(1, 0), partly_empty);
+        world.insert_chunk(Coord(0, 1), ChunkData::new(2));
+        world.insert_chunk(Coord(-1, -1), ChunkData::default());
+
+        assert_eq!(world.trim(), 2);
+        assert_eq!(world.len(), 2);
+        assert!(world.get_chunk(Coord(0, 0)).is_none());
+        assert!(world.get_chunk(Coord(-1, -1)).is_none());
+        assert_eq!(world.get_chunk(Coord(1, 0)), Some(&partly_empty));
+        assert!(world.get_chunk(Coord(0, 1)).is_some());
+        assert!(!world.is_dirty(Coord(0, 0)));
+        assert!(!world.is_dirty(Coord(-1, -1)));
+        assert!(world.is_dirty(Coord(1, 0)));
+        assert_eq!(world.trim(), 0);
+    }
+
+    #[test]
+    fn coord_display_round_trips_through_from_str() {
+        for &coord in &[Coord(0, 0), Coord(-4, 17), Coord(isize::MIN, isize::MAX)] {
+            assert_eq!(coord.to_string().parse::<Coord>(), Ok(coord));
+        }
+        assert_eq!("  ( -3 ,2 ) ".parse::<Coord>(), Ok(Coord(-3, 2)));
+    }
+
+    #[test]
+    fn coord_from_str_rejects_malformed_input() {
+        for &input in &[
+            "(1,)",
+            "(,1)",
+            "1, 2",
+            "(1, 2",
+            "(1; 2)",
+            "(a, 2)",
+            "(1, 2, 3)",
+            "",
+        ] {
+            assert_eq!(input.parse::<Coord>(), Err(CoordParseError), "{:?}", input);
+        }
+    }
+
+    #[test]
+    fn changed_rows_of_a_single_row_edit() {
+        let a = ChunkData::<3, 4>::default();
+        let mut b = a;
+        b[2][1] = 7;
+        assert_eq!(a.changed_rows(&b), vec![2]);
+        assert_eq!(a.changed_rows(&a), Vec::<usize>::new());
+    }
+
+    #[test]
+    fn changed_rows_of_several_row_edits() {
+        let a = ChunkData::<3, 4>::new(1);
+        let mut b = a;
+        b[0][0] = 2;
+        b[3][2] = 2;
+        b[3][1] = 2;
+        assert_eq!(a.changed_rows(&b), vec![0, 3]);
+        assert_eq!(b.changed_rows(&a), vec![0, 3]);
+    }
+
+    #[test]
+    fn coord_default_is_the_origin() {
+        assert_eq!(Coord::default(), Coord::new(0, 0));
+    }
+
+    #[test]
+    fn shift_origin_moves_every_chunk() {
+        let mut world = World::<2, 2>::new();
+        world.insert_chunk(Coord(0, 0), ChunkData::new(1));
+        world.insert_chunk(Coord(1, 0), ChunkData::new(2));
+        world.insert_chunk(Coord(0, -1), ChunkData::new(3));
+        world.take_dirty().for_each(drop);
+
+        world.shift_origin(Coord(-1, 2));
+        assert_eq!(world.len(), 3);
+        assert_eq!(world.get_chunk(Coord(-1, 2)), Some(&ChunkData::new(1)));
+        assert_eq!(world.get_chunk(Coord(0, 2)), Some(&ChunkData::new(2)));
+        assert_eq!(world.get_chunk(Coord(-1, 1)), Some(&ChunkData::new(3)));
+        assert!(world.get_chunk(Coord(1, 0)).is_none());
+        assert_eq!(world.get_block(Coord(-2, 4)), Some(1));
+        assert_eq!(world.dirty_len(), 3);
+        assert!(world.is_dirty(Coord(0, 2)));
+        assert!(!world.is_dirty(Coord(1, 0)));
+    }
+
+    #[test]
+    #[should_panic(expected = "overflowed")]
+    fn shift_origin_panics_on_overflow() {
+        let mut world = World::<2, 2>::new();
+        world.insert_chunk(Coord(isize::MAX, 0), ChunkData::default());
+        world.shift_origin(Coord(1, 0));
+    }
+
+    #[test]
+    fn iter_runs_splits_a_row_into_maximal_runs() {
+        let mut chunk = ChunkData::<6, 1>::default();
+        chunk.as_array_mut()[0] = [1, 1, 2, 2, 2, 1];
+        let runs: Vec<_> = chunk.iter_runs().collect();
+        assert_eq!(
+            runs,
+            vec![
+                (Coord(0, 0), 2, 1),
+                (Coord(2, 0), 3, 2),
+                (Coord(5, 0), 1, 1)
+            ]
+        );
+    }
+
+    #[test]
+    fn iter_runs_never_cross_rows() {
+        let chunk = ChunkData::<3, 2>::new(4);
+        let runs: Vec<_> = chunk.iter_runs().collect();
+        assert_eq!(runs, vec![(Coord(0, 0), 3, 4), (Coord(0, 1), 3, 4)]);
+    }
+
+    #[test]
+    fn midpoint_of_even_and_odd_spans() {
+        assert_eq!(Coord(0, 2).midpoint(Coord(4, 8)), Coord(2, 5));
+        assert_eq!(Coord(1, 0).midpoint(Coord(4, 3)), Coord(2, 1));
+        assert_eq!(Coord(-1, -4).midpoint(Coord(-4, -1)), Coord(-2, -2));
+    }
+
+    #[test]
+    fn midpoint_across_zero_rounds_toward_zero() {
+        assert_eq!(Coord(-3, -6).midpoint(Coord(4, 3)), Coord(0, -1));
+        assert_eq!(Coord(-5, 5).midpoint(Coord(5, -5)), Coord(0, 0));
+        assert_eq!(
+            Coord(isize::MAX, isize::MIN).midpoint(Coord(isize::MAX, isize::MIN)),
+            Coord(isize::MAX, isize::MIN)
+        );
+    }
+
+    #[test]
+    fn remap_blocks_rewrites_every_occurrence() {
+        let mut world = World::<2, 2>::new();
+        let mut a = ChunkData::new(1);
+        a[0][1] = 2;
+        let mut b = ChunkData::new(3);
+        b[1][1] = 1;
+        world.insert_chunk(Coord(0, 0), a);
+        world.insert_chunk(Coord(1, 0), b);
+        world.insert_chunk(Coord(0, 1), ChunkData::new(5));
+        world.take_dirty().for_each(drop);
+
+        let mapping: HashMap<_, _> = vec![(1, 10), (2, 1), (5, 5)].into_iter().collect();
+        world.remap_blocks(&mapping);
+
+        let a = world.get_chunk(Coord(0, 0)).unwrap();
+        assert_eq!(a.as_array(), &[[10, 1], [10, 10]]);
+        let b = world.get_chunk(Coord(1, 0)).unwrap();
+        assert_eq!(b.as_array(), &[[3, 3], [3, 10]]);
+        assert!(world.is_dirty(Coord(0, 0)));
+        assert!(world.is_dirty(Coord(1, 0)));
+        assert!(!world.is_dirty(Coord(0, 1)));
+    }
+
+    #[test]
+    fn symmetry_checks_for_both_axes() {
+        let mut chunk = ChunkData::<3, 4>::default();
+        chunk
+            .as_array_mut()
+            .copy_from_slice(&[[1, 2, 1], [3, 4, 3], [3, 4, 3], [1, 2, 1]]);
+        assert!(chunk.is_horizontally_symmetric());
+        assert!(chunk.is_vertically_symmetric());
+
+        chunk[0][0] = 9;
+        assert!(!chunk.is_horizontally_symmetric());
+        assert!(!chunk.is_vertically_symmetric());
+
+        let mut chunk = ChunkData::<3, 3>::default();
+        chunk
+            .as_array_mut()
+            .copy_from_slice(&[[1, 2, 1], [5, 6, 5], [7, 8, 7]]);
+        assert!(chunk.is_horizontally_symmetric());
+        assert!(!chunk.is_vertically_symmetric());
+        chunk
+            .as_array_mut()
+            .copy_from_slice(&[[1, 2, 3], [4, 5, 6], [1, 2, 3]]);
+        assert!(!chunk.is_horizontally_symmetric());
+        assert!(chunk.is_vertically_symmetric());
+    }
+
+    #[test]
+    fn packed_coords_round_trip_across_width_boundaries() {
+        let cases = [
+            (Coord(0, 0), 1),
+            (Coord(127, -128), 1),
+            (Coord(128, 0), 2),
+            (Coord(0, -129), 2),
+            (Coord(-32768, 32767), 2),
+            (Coord(32768, 0), 4),
+            (Coord(0, -32769), 4),
+            (Coord(i32::MIN as isize, i32::MAX as isize), 4),
+            (Coord(i32::MAX as isize + 1, 0), 8),
+            (Coord(isize::MIN, isize::MAX), 8),
+        ];
+        for &(coord, width) in &cases {
+            assert_eq!(coord.min_bytes(), width, "{}", coord);
+            let mut bytes = Vec::new();
+            coord.write_packed(&mut bytes).unwrap();
+            assert_eq!(bytes.len(), 1 + 2 * width as usize);
+            assert_eq!(Coord::read_packed(&mut &bytes[..]).unwrap(), coord);
+        }
+    }
+
+    #[test]
+    fn read_packed_rejects_bad_tags_and_truncation() {
+        assert!(Coord::read_packed(&mut &[3, 0, 0, 0, 0, 0, 0][..]).is_err());
+        assert!(Coord::read_packed(&mut &[2, 0, 0, 0][..]).is_err());
+    }
+
+    #[test]
+    fn consuming_iterator_yields_every_cell_in_row_major_order() {
+        let mut chunk = ChunkData::<3, 2>::default();
+        chunk
+            .as_array_mut()
+            .copy_from_slice(&[[1, 2, 3], [4, 5, 6]]);
+        let iter = chunk.into_iter();
+        assert_eq!(iter.len(), 6);
+        let cells: Vec<_> = iter.collect();
+        assert_eq!(
+            cells,
+            vec![
+                (Coord(0, 0), 1),
+                (Coord(1, 0), 2),
+                (Coord(2, 0), 3),
+                (Coord(0, 1), 4),
+                (Coord(1, 1), 5),
+                (Coord(2, 1), 6),
+            ]
+        );
+    }
+
+    #[test]
+    fn chunk_at_world_uses_floor_decomposition() {
+        let mut world = World::<4, 3>::new();
+        world.insert_chunk(Coord(0, 0), ChunkData::new(1));
+        world.insert_chunk(Coord(-1, -1), ChunkData::new(2));
+        world.insert_chunk(Coord(2, -1), ChunkData::new(3));
+
+        let (chunk, local) = world.chunk_at_world(Coord(3, 2)).unwrap();
+        assert_eq!((chunk[0][0], local), (1, Coord(3, 2)));
+        let (chunk, local) = world.chunk_at_world(Coord(-1, -1)).unwrap();
+        assert_eq!((chunk[0][0], local), (2, Coord(3, 2)));
+        let (chunk, local) = world.chunk_at_world(Coord(-4, -3)).unwrap();
+        assert_eq!((chunk[0][0], local), (2, Coord(0, 0)));
+        let (chunk, local) = world.chunk_at_world(Coord(9, -2)).unwrap();
+        assert_eq!((chunk[0][0], local), (3, Coord(1, 1)));
+        assert!(world.chunk_at_world(Coord(-5, 0)).is_none());
+        assert!(world.chunk_at_world(Coord(4, 0)).is_none());
+    }
+
+    #[test]
+    fn contains_finds_present_ids_only() {
+        let mut chunk = ChunkData::<3, 3>::new(1);
+        chunk[2][1] = 6;
+        assert!(chunk.contains(1));
+        assert!(chunk.contains(6));
+        assert!(!chunk.contains(0));
+        assert!(chunk[2].contains(6));
+        assert!(!chunk[1].contains(6));
+    }
+
+    #[test]
+    fn reduced_direction_divides_by_the_gcd() {
+        assert_eq!(Coord(4, 2).reduced_direction(), Coord(2, 1));
+        assert_eq!(Coord(0, -3).reduced_direction(), Coord(0, -1));
+        assert_eq!(Coord(-6, 9).reduced_direction(), Coord(-2, 3));
+        assert_eq!(Coord(5, 7).reduced_direction(), Coord(5, 7));
+        assert_eq!(Coord(0, 0).reduced_direction(), Coord(0, 0));
+    }
+
+    #[test]
+    fn world_save_load_round_trip() {
+        let mut world = World::<3, 2>::new();
+        let mut chunk = ChunkData::new(4);
+        chunk[1][2] = 300;
+        world.insert_chunk(Coord(0, 0), chunk);
+        world.insert_chunk(Coord(-7, 2), ChunkData::new(1));
+        world.insert_chunk(Coord(40_000, -129), ChunkData::default());
+
+        let mut cursor = io::Cursor::new(Vec::new());
+        world.save_to(&mut cursor).unwrap();
+        cursor.set_position(0);
+        let loaded = World::<3, 2>::load_from(&mut cursor).unwrap();
+        assert_eq!(loaded, world);
+        assert_eq!(loaded.get_block(Coord(2, 1)), Some(300));
+        assert_eq!(loaded.dirty_len(), 0);
+        assert_eq!(cursor.position() as usize, cursor.get_ref().len());
+    }
+
+    #[test]
+    fn world_load_detects_truncation_and_bad_magic() {
+        let mut world = World::<3, 2>::new();
+        world.insert_chunk(Coord(1, 1), ChunkData::new(2));
+        world.insert_chunk(Coord(2, 1), ChunkData::new(3));
+        let mut bytes = Vec::new();
+        world.save_to(&mut bytes).unwrap();
+
+        for len in 0..bytes.len() {
+            let error = World::<3, 2>::load_from(&mut &bytes[..len]).unwrap_err();
+            assert_eq!(error.kind(), io::ErrorKind::UnexpectedEof, "length {}", len);
+        }
+        bytes[0] = b'X';
+        let error = World::<3, 2>::load_from(&mut &bytes[..]).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn count_where_with_a_predicate_on_y() {
+        let mut chunk = ChunkData::<4, 4>::default();
+        chunk[0][0] = 1;
+        chunk[2][1] = 1;
+        chunk[3][3] = 1;
+        chunk[3][0] = 2;
+        assert_eq!(chunk.count_where(|c, block| c.y() >= 2 && block != 0), 3);
+        assert_eq!(chunk.count_where(|c, _| c.y() < 2), 8);
+    }
+
+    #[test]
+    fn snap_rounds_toward_negative_infinity() {
+        assert_eq!(Coord(7, 13).snap(4, 5), Coord(4, 10));
+        assert_eq!(Coord(-1, -6).snap(4, 5), Coord(-4, -10));
+        assert_eq!(Coord(8, -10).snap(4, 5), Coord(8, -10));
+        assert_eq!(Coord(0, 0).snap(4, 5), Coord(0, 0));
+    }
+
+    #[test]
+    #[should_panic(expected = "grid size must be non-zero")]
+    fn snap_rejects_an_empty_grid() {
+        Coord(1, 1).snap(0, 2);
+    }
+
+    #[test]
+    fn nearest_block_finds_the_closest_ring() {
+        let mut world = World::<4, 4>::new();
+        for &chunk in &[Coord(0, 0), Coord(-1, 0), Coord(0, -1), Coord(-1, -1)] {
+            world.insert_chunk(chunk, ChunkData::default());
+        }
+        world.set_block(Coord(3, -2), 7);
+        world.set_block(Coord(-1, 1), 7);
+        world.set_block(Coord(3, 3), 8);
+
+        assert_eq!(world.nearest_block(Coord(2, -1), 7, 4), Some(Coord(3, -2)));
+        assert_eq!(world.nearest_block(Coord(1, 1), 7, 4), Some(Coord(-1, 1)));
+        assert_eq!(world.nearest_block(Coord(-2, 1), 7, 4), Some(Coord(-1, 1)));
+        assert_eq!(world.nearest_block(Coord(0, 0), 8, 3), Some(Coord(3, 3)));
+        assert_eq!(world.nearest_block(Coord(3, 3), 8, 0), Some(Coord(3, 3)));
+    }
+
+    #[test]
+    fn nearest_block_stops_at_max_radius() {
+        let mut world = World::<4, 4>::new();
+        world.insert_chunk(Coord(0, 0), ChunkData::default());
+        world.set_block(Coord(3, 0), 7);
+        assert_eq!(world.nearest_block(Coord(0, 0), 7, 2), None);
+        assert_eq!(world.nearest_block(Coord(0, 0), 7, 3), Some(Coord(3, 0)));
+    }
+
+    fn numbered<const X: usize, const Y: usize>() -> ChunkData<X, Y> {
+        let mut chunk = ChunkData::default();
+        for (coord, cell) in chunk.iter_mut() {
+            *cell = 1 + coord.y() as usize * X + coord.x() as usize;
+        }
+        chunk
+    }
+
+    #[test]
+    fn scroll_with_fill_on_each_axis() {
+        let mut chunk = numbered::<3, 2>();
+        chunk.scroll(1, 0, Some(0));
+        assert_eq!(chunk.as_array(), &[[0, 1, 2], [0, 4, 5]]);
+        let mut chunk = numbered::<3, 2>();
+        chunk.scroll(-2, 0, Some(0));
+        assert_eq!(chunk.as_array(), &[[3, 0, 0], [6, 0, 0]]);
+        let mut chunk = numbered::<3, 2>();
+        chunk.scroll(0, 1, Some(9));
+        assert_eq!(chunk.as_array(), &[[9, 9, 9], [1, 2, 3]]);
+        let mut chunk = numbered::<3, 2>();
+        chunk.scroll(0, -1, Some(9));
+        assert_eq!(chunk.as_array(), &[[4, 5, 6], [9, 9, 9]]);
+    }
+
+    #[test]
+    fn scroll_with_wrap_on_each_axis() {
+        let mut chunk = numbered::<3, 2>();
+        chunk.scroll(1, 0, None);
+        assert_eq!(chunk.as_array(), &[[3, 1, 2], [6, 4, 5]]);
+        let mut chunk = numbered::<3, 2>();
+        chunk.scroll(-1, 0, None);
+        assert_eq!(chunk.as_array(), &[[2, 3, 1], [5, 6, 4]]);
+        let mut chunk = numbered::<3, 2>();
+        chunk.scroll(0, 1, None);
+        assert_eq!(chunk.as_array(), &[[4, 5, 6], [1, 2, 3]]);
+        let mut chunk = numbered::<3, 2>();
+        chunk.scroll(0, -3, None);
+        assert_eq!(chunk.as_array(), &[[4, 5, 6], [1, 2, 3]]);
+    }
+
+    #[test]
+    fn eq_flat_compares_against_the_row_major_cells() {
+        let chunk = numbered::<3, 2>();
+        assert!(chunk.eq_flat(&[1, 2, 3, 4, 5, 6]));
+        assert!(!chunk.eq_flat(&[1, 2, 3, 4, 6, 5]));
+        assert!(!chunk.eq_flat(&[1, 2, 3, 4, 5]));
+        assert!(!chunk.eq_flat(&[1, 2, 3, 4, 5, 6, 7]));
+    }
+
+    #[test]
+    fn to_usize_requires_both_components_non_negative() {
+        assert_eq!(Coord(0, 0).to_usize(), Some((0, 0)));
+        assert_eq!(Coord(3, 12).to_usize(), Some((3, 12)));
+        assert_eq!(Coord(-1, 2).to_usize(), None);
+        assert_eq!(Coord(2, -1).to_usize(), None);
+        assert_eq!(
+            Coord(isize::MAX, 0).to_usize(),
+            Some((isize::MAX as usize, 0))
+        );
+    }
+
+    #[test]
+    fn set_block_autoload_creates_missing_chunks() {
+        let mut world = World::<4, 4>::new();
+        let previous = world.set_block_autoload(Coord(-3, 5), 8, |chunk| {
+            assert_eq!(chunk, Coord(-1, 1));
+            ChunkData::default()
+        });
+        assert_eq!(previous, 0);
+        assert_eq!(world.len(), 1);
+        assert!(world.is_dirty(Coord(-1, 1)));
+        let chunk = world.get_chunk(Coord(-1, 1)).unwrap();
+        assert_eq!(chunk.count_where(|_, block| block != 0), 1);
+        assert_eq!(chunk[1][1], 8);
+
+        world.take_dirty().for_each(drop);
+        let previous = world.set_block_autoload(Coord(-4, 4), 2, |_| unreachable!());
+        assert_eq!(previous, 0);
+        assert_eq!(world.get_block(Coord(-4, 4)), Some(2));
+        assert!(world.is_dirty(Coord(-1, 1)));
+    }
+
+    #[test]
+    fn quadrants_split_a_chunk_into_quarters() {
+        let chunk = numbered::<4, 4>();
+        let [nw, ne, sw, se] = chunk.quadrants::<2, 2>().unwrap();
+        assert_eq!(nw.as_array(), &[[1, 2], [5, 6]]);
+        assert_eq!(ne.as_array(), &[[3, 4], [7, 8]]);
+        assert_eq!(sw.as_array(), &[[9, 10], [13, 14]]);
+        assert_eq!(se.as_array(), &[[11, 12], [15, 16]]);
+        assert!(chunk.quadrants::<2, 1>().is_none());
+    }
+
+    #[test]
+    fn reflection_across_non_zero_axes() {
+        assert_eq!(Coord(1, 4).reflect_x(3), Coord(5, 4));
+        assert_eq!(Coord(-2, 4).reflect_y(-1), Coord(-2, -6));
+        assert_eq!(Coord(3, 7).reflect_x(3), Coord(3, 7));
+    }
+
+    #[test]
+    fn double_reflection_is_the_identity() {
+        for &c in &[Coord(0, 0), Coord(9, -4), Coord(-5, 11)] {
+            assert_eq!(c.reflect_x(2).reflect_x(2), c);
+            assert_eq!(c.reflect_y(-7).reflect_y(-7), c);
+        }
+    }
+
+    #[test]
+    fn connected_components_of_two_regions() {
+        let mut chunk = ChunkData::<4, 3>::default();
+        chunk
+            .as_array_mut()
+            .copy_from_slice(&[[1, 1, 0, 1], [0, 1, 0, 1], [1, 0, 0, 1]]);
+        let mut components = chunk.connected_components(|block| block == 1);
+        for component in &mut components {
+            component.sort_by_key(|c| (c.1, c.0));
+        }
+        assert_eq!(
+            components,
+            vec![
+                vec![Coord(0, 0), Coord(1, 0), Coord(1, 1)],
+                vec![Coord(3, 0), Coord(3, 1), Coord(3, 2)],
+                vec![Coord(0, 2)],
+            ]
+        );
+    }
+
+    #[derive(Serialize, Deserialize, PartialEq, Debug)]
+    struct SparseChunk {
+        #[serde(with = "sparse_rows")]
+        chunk: ChunkData<4, 4>,
+    }
+
+    #[test]
+    fn sparse_rows_round_trip_and_shrink_sparse_chunks() {
+        let mut chunk = ChunkData::default();
+        chunk[2][1] = 3;
+        let sparse = SparseChunk { chunk };
+        let value = test_value::to_value(&sparse).unwrap();
+        let back: SparseChunk = test_value::from_value(value.clone()).unwrap();
+        assert_eq!(back, sparse);
+
+        let full = test_value::to_value(&chunk).unwrap();
+        assert!(value.size() < full.size());
+    }
+
+    #[test]
+    fn orientation_of_three_points() {
+        let (a, b) = (Coord(0, 0), Coord(4, 0));
+        assert_eq!(Coord::orientation(a, b, Coord(8, 0)), Ordering::Equal);
+        assert_eq!(Coord::orientation(a, b, Coord(-3, 0)), Ordering::Equal);
+        // With y pointing south, turning from east towards south is clockwise.
+        assert_eq!(Coord::orientation(a, b, Coord(4, 3)), Ordering::Greater);
+        assert_eq!(Coord::orientation(a, b, Coord(4, -3)), Ordering::Less);
+        assert_eq!(Coord(2, 1).cross(Coord(3, 4)), 5);
+    }
+
+    #[test]
+    fn downsample_takes_the_mode_of_each_block() {
+        let mut chunk = ChunkData::<4, 4>::default();
+        chunk.as_array_mut().copy_from_slice(&[
+            [1, 1, 2, 3],
+            [1, 4, 3, 2],
+            [5, 5, 7, 7],
+            [6, 6, 7, 7],
+        ]);
+        let small = chunk.downsample::<2, 2>().unwrap();
+        // The top-right and bottom-left blocks are ties, which go to the smaller id.
+        assert_eq!(small.as_array(), &[[1, 2], [5, 7]]);
+        assert!(chunk.downsample::<3, 3>().is_none());
+        assert!(chunk.downsample::<2, 1>().is_none());
+    }
+
+    #[test]
+    fn iter_region_chunks_yields_only_chunks_inside() {
+        let mut world = World::<2, 2>::new();
+        for y in -2..=2 {
+            for x in -2..=2 {
+                if (x + y) % 2 == 0 {
+                    world.insert_chunk(Coord(x, y), ChunkData::default());
+                }
+            }
+        }
+        let mut inside: Vec<_> = world
+            .iter_region_chunks(Coord(-1, -1), Coord(1, 0))
+            .map(|(coord, _)| coord)
+            .collect();
+        inside.sort_by_key(|c| (c.1, c.0));
+        assert_eq!(inside, vec![Coord(-1, -1), Coord(1, -1), Coord(0, 0)]);
+        assert_eq!(
+            world.iter_region_chunks(Coord(1, 0), Coord(-1, -1)).count(),
+            3
+        );
+        assert_eq!(
+            world.iter_region_chunks(Coord(5, 5), Coord(9, 9)).count(),
+            0
+        );
+    }
+
+    #[test]
+    fn splice_within_and_at_the_end_of_a_row() {
+        let mut row = ChunkDataRow::<5>::default();
+        row.splice(1, &[7, 8]).unwrap();
+        assert_eq!(row.0, [0, 7, 8, 0, 0]);
+        row.splice(3, &[1, 2]).unwrap();
+        assert_eq!(row.0, [0, 7, 8, 1, 2]);
+        row.splice(5, &[]).unwrap();
+        assert_eq!(row.0, [0, 7, 8, 1, 2]);
+    }
+
+    #[test]
+    fn splice_past_the_end_leaves_the_row_unchanged() {
+        let mut row = ChunkDataRow::<5>::new(3);
+        assert_eq!(row.splice(4, &[1, 2]), Err(OutOfBoundsError));
+        assert_eq!(row.splice(6, &[]), Err(OutOfBoundsError));
+        assert_eq!(row.splice(usize::MAX, &[1]), Err(OutOfBoundsError));
+        assert_eq!(row, ChunkDataRow::new(3));
+    }
+
+    #[test]
+    fn aabb_containment() {
+        let aabb = Aabb::new(Coord(3, -1), Coord(-2, 4));
+        assert_eq!((aabb.min, aabb.max), (Coord(-2, -1), Coord(3, 4)));
+        assert_eq!((aabb.width(), aabb.height()), (6, 6));
+        assert!(aabb.contains(Coord(-2, -1)));
+        assert!(aabb.contains(Coord(3, 4)));
+        assert!(aabb.contains(Coord(0, 0)));
+        assert!(!aabb.contains(Coord(4, 0)));
+        assert!(!aabb.contains(Coord(0, -2)));
+    }
+
+    #[test]
+    fn aabb_intersection_of_overlapping_and_disjoint_boxes() {
+        let a = Aabb::new(Coord(0, 0), Coord(4, 4));
+        let b = Aabb::new(Coord(3, 2), Coord(6, 8));
+        assert_eq!(
+            a.intersection(&b),
+            Some(Aabb::new(Coord(3, 2), Coord(4, 4)))
+        );
+        assert!(a.intersects(&b));
+        let c = Aabb::new(Coord(5, 0), Coord(6, 1));
+        assert_eq!(a.intersection(&c), None);
+        assert!(!a.intersects(&c));
+        let touching = Aabb::new(Coord(4, 4), Coord(9, 9));
+        assert_eq!(
+            a.intersection(&touching),
+            Some(Aabb::new(Coord(4, 4), Coord(4, 4)))
+        );
+        assert_eq!(a.union(&c), Aabb::new(Coord(0, 0), Coord(6, 4)));
+    }
+
+    #[test]
+    fn aabb_iter_is_row_major() {
+        let cells: Vec<_> = Aabb::new(Coord(1, -1), Coord(2, 0)).iter().collect();
+        assert_eq!(
+            cells,
+            vec![Coord(1, -1), Coord(2, -1), Coord(1, 0), Coord(2, 0)]
+        );
+    }
+
+    #[test]
+    fn rotating_a_2x3_chunk_moves_each_cell() {
+        // 2 wide, 3 tall:
+        // 1 2
+        // 3 4
+        // 5 6
+        let chunk = numbered::<2, 3>();
+        assert_eq!(chunk.rotate_cw().as_array(), &[[5, 3, 1], [6, 4, 2]]);
+        assert_eq!(chunk.rotate_ccw().as_array(), &[[2, 4, 6], [1, 3, 5]]);
+    }
+
+    #[test]
+    fn four_chunk_rotations_are_the_identity() {
+        let chunk = numbered::<2, 3>();
+        assert_eq!(chunk.rotate_cw().rotate_cw().rotate_cw().rotate_cw(), chunk);
+        assert_eq!(
+            chunk.rotate_ccw().rotate_ccw().rotate_ccw().rotate_ccw(),
+            chunk
+        );
+        assert_eq!(chunk.rotate_cw().rotate_ccw(), chunk);
+    }
+
+    fn allocations<const X: usize, const Y: usize>(world: &World<X, Y>) -> usize {
+        let pointers: HashSet<_> = world.chunks.values().map(Arc::as_ptr).collect();
+        pointers.len()
+    }
+
+    #[test]
+    fn compact_shares_equal_chunks() {
+        let mut world = World::<4, 4>::new();
+        for x in 0..10 {
+            world.insert_chunk(Coord(x, 0), ChunkData::default());
+        }
+        world.insert_chunk(Coord(0, 1), ChunkData::new(2));
+        world.insert_chunk(Coord(1, 1), ChunkData::new(2));
+        world.insert_chunk(Coord(2, 1), ChunkData::new(3));
+        assert_eq!(allocations(&world), 13);
+
+        let before = world.clone();
+        assert_eq!(world.compact(), 10);
+        assert_eq!(allocations(&world), 3);
+        assert_eq!(world, before);
+        assert_eq!(world.compact(), 0);
+    }
+
+    #[test]
+    fn writes_to_a_shared_chunk_copy_it_first() {
+        let mut world = World::<4, 4>::new();
+        for x in 0..3 {
+            world.insert_chunk(Coord(x, 0), ChunkData::default());
+        }
+        world.compact();
+        world.take_dirty().for_each(drop);
+
+        world.set_block(Coord(5, 1), 9);
+        assert_eq!(world.get_block(Coord(5, 1)), Some(9));
+        assert_eq!(world.get_block(Coord(1, 1)), Some(0));
+        assert_eq!(world.get_block(Coord(9, 1)), Some(0));
+        assert_eq!(allocations(&world), 2);
+        assert_eq!(world.dirty_len(), 1);
+
+        let snapshot = world.clone();
+        world.get_chunk_mut(Coord(0, 0)).unwrap().fill(4);
+        assert_eq!(snapshot.get_block(Coord(0, 0)), Some(0));
+        assert_eq!(world.get_block(Coord(0, 0)), Some(4));
+    }
+
+    #[test]
+    fn within_radius_includes_cells_up_to_the_radius() {
+        let center = Coord(2, -3);
+        let cells: HashSet<_> = center.within_radius(5).collect();
+        assert!(cells.contains(&center));
+        assert!(cells.contains(&Coord(2 + 3, -3 + 4)));
+        assert!(cells.contains(&Coord(2 - 5, -3)));
+        assert!(!cells.contains(&Coord(2 + 4, -3 + 4)));
+        assert!(!cells.contains(&Coord(2, -3 + 6)));
+        assert!(cells.iter().all(|c| c.distance_squared(center) <= 25));
+        assert_eq!(
+            Coord(0, 0).within_radius(0).collect::<Vec<_>>(),
+            vec![Coord(0, 0)]
+        );
+    }
+
+    #[test]
+    fn pad_places_the_chunk_at_an_offset() {
+        let chunk = numbered::<2, 2>();
+        let padded = chunk.pad::<4, 4>(Coord(1, 2), 9).unwrap();
+        assert_eq!(
+            padded.as_array(),
+            &[[9, 9, 9, 9], [9, 9, 9, 9], [9, 1, 2, 9], [9, 3, 4, 9]]
+        );
+    }
+
+    #[test]
+    fn pad_rejects_offsets_that_do_not_fit() {
+        let chunk = numbered::<2, 2>();
+        assert!(chunk.pad::<4, 4>(Coord(2, 2), 0).is_some());
+        assert!(chunk.pad::<4, 4>(Coord(3, 0), 0).is_none());
+        assert!(chunk.pad::<4, 4>(Coord(0, 3), 0).is_none());
+        assert!(chunk.pad::<4, 4>(Coord(-1, 0), 0).is_none());
+        assert!(chunk.pad::<1, 4>(Coord(0, 0), 0).is_none());
+    }
+
+    #[test]
+    fn generate_region_fills_missing_chunks_only() {
+        let mut world = World::<2, 2>::new();
+        world.insert_chunk(Coord(0, 0), ChunkData::new(7));
+        world.take_dirty().for_each(drop);
+
+        let mut calls = Vec::new();
+        world.generate_region(Coord(-1, -1), Coord(1, 1), |coord| {
+            calls.push(coord);
+            ChunkData::new(1)
+        });
+        assert_eq!(world.len(), 9);
+        assert_eq!(calls.len(), 8);
+        assert!(!calls.contains(&Coord(0, 0)));
+        assert_eq!(world.get_chunk(Coord(0, 0)), Some(&ChunkData::new(7)));
+        assert_eq!(world.get_chunk(Coord(1, -1)), Some(&ChunkData::new(1)));
+        assert_eq!(world.dirty_len(), 8);
+        assert!(!world.is_dirty(Coord(0, 0)));
+    }
+
+    #[test]
+    fn tile_checksums_change_only_for_the_edited_tile() {
+        let mut chunk = numbered::<4, 4>();
+        let before = chunk.tile_checksums::<2>();
+        assert_eq!(before.len(), 4);
+        chunk[3][0] = 99;
+        let after = chunk.tile_checksums::<2>();
+        assert_eq!(after[0], before[0]);
+        assert_eq!(after[1], before[1]);
+        assert_ne!(after[2], before[2]);
+        assert_eq!(after[3], before[3]);
+    }
+
+    #[derive(Serialize, Deserialize, PartialEq, Debug)]
+    struct KeyedChunks {
+        #[serde(with = "coord_key")]
+        chunks: HashMap<Coord, ChunkData<2, 2>>,
+    }
+
+    #[test]
+    fn coord_key_round_trips_through_string_keys() {
+        let mut chunks = HashMap::new();
+        chunks.insert(Coord(0, 0), ChunkData::new(1));
+        chunks.insert(Coord(-3, 12), ChunkData::new(2));
+        let keyed = KeyedChunks { chunks };
+        let value = test_value::to_value(&keyed).unwrap();
+
+        let test_value::Value::Map(fields) = &value else {
+            panic!("expected a map, got {:?}", value);
+        };
+        let test_value::Value::Map(entries) = &fields[0].1 else {
+            panic!("expected a map, got {:?}", fields[0].1);
+        };
+        let mut keys: Vec<_> = entries.iter().map(|(key, _)| key.clone()).collect();
+        keys.sort_by_key(|key| format!("{:?}", key));
+        assert_eq!(
+            keys,
+            vec![
+                test_value::Value::Str("-3,12".to_string()),
+                test_value::Value::Str("0,0".to_string()),
+            ]
+        );
+
+        let back: KeyedChunks = test_value::from_value(value).unwrap();
+        assert_eq!(back, keyed);
+    }
+
+    #[test]
+    fn coord_key_rejects_malformed_keys() {
+        use test_value::Value;
+        let value = Value::Map(vec![(
+            Value::Str("chunks".to_string()),
+            Value::Map(vec![(
+                Value::Str("1;2".to_string()),
+                test_value::to_value(&ChunkData::<2, 2>::default()).unwrap(),
+            )]),
+        )]);
+        assert!(test_value::from_value::<KeyedChunks>(value).is_err());
+    }
+
+    #[test]
+    fn set_border_on_a_square_chunk() {
+        let mut chunk = ChunkData::<3, 3>::default();
+        chunk.set_border(1);
+        assert_eq!(chunk.as_array(), &[[1, 1, 1], [1, 0, 1], [1, 1, 1]]);
+    }
+
+    #[test]
+    fn set_border_on_non_square_chunks() {
+        let mut chunk = ChunkData::<4, 3>::new(5);
+        chunk.set_border(2);
+        assert_eq!(
+            chunk.as_array(),
+            &[[2, 2, 2, 2], [2, 5, 5, 2], [2, 2, 2, 2]]
+        );
+        let mut chunk = ChunkData::<2, 4>::new(5);
+        chunk.set_border(2);
+        assert_eq!(chunk.is_uniform(), Some(2));
+        let mut chunk = ChunkData::<3, 1>::new(5);
+        chunk.set_border(2);
+        assert_eq!(chunk.is_uniform(), Some(2));
+    }
+
+    #[test]
+    fn iter_blocks_covers_every_loaded_cell_in_world_space() {
+        let mut world = World::<3, 2>::new();
+        world.insert_chunk(Coord(0, 0), numbered());
+        world.insert_chunk(Coord(-1, 2), ChunkData::new(8));
+        let blocks: HashMap<_, _> = world.iter_blocks().collect();
+        assert_eq!(world.iter_blocks().count(), 2 * 3 * 2);
+        assert_eq!(blocks.len(), 2 * 3 * 2);
+        assert_eq!(blocks[&Coord(0, 0)], 1);
+        assert_eq!(blocks[&Coord(2, 1)], 6);
+        assert_eq!(blocks[&Coord(-3, 4)], 8);
+        assert_eq!(blocks[&Coord(-1, 5)], 8);
+        assert!(!blocks.contains_key(&Coord(3, 0)));
+    }
+
+    #[test]
+    fn i16_and_i32_conversions_at_the_boundaries() {
+        let min = Coord::from_i16(i16::MIN, i16::MAX);
+        assert_eq!(min, Coord(-32768, 32767));
+        assert_eq!(min.try_to_i16(), Some((i16::MIN, i16::MAX)));
+        assert_eq!(Coord(32768, 0).try_to_i16(), None);
+        assert_eq!(Coord(0, -32769).try_to_i16(), None);
+        assert_eq!(Coord(32768, -32769).try_to_i32(), Some((32768, -32769)));
+        assert_eq!(
+            Coord(i32::MIN as isize, i32::MAX as isize).try_to_i32(),
+            Some((i32::MIN, i32::MAX))
+        );
+    }
+
+    #[test]
+    #[cfg(target_pointer_width = "64")]
+    fn try_to_i32_rejects_out_of_range_components() {
+        assert_eq!(Coord(i32::MAX as isize + 1, 0).try_to_i32(), None);
+        assert_eq!(Coord(0, i32::MIN as isize - 1).try_to_i32(), None);
+    }
+
+    #[test]
+    fn apply_palette_remaps_every_cell() {
+        let mut chunk = ChunkData::<2, 2>::default();
+        chunk[0][1] = 1;
+        chunk[1][0] = 2;
+        assert_eq!(chunk.apply_palette(&[7, 8, 9]), Ok(()));
+        assert_eq!(chunk.as_array(), &[[7, 8], [9, 7]]);
+    }
+
+    #[test]
+    fn apply_palette_leaves_the_chunk_unchanged_on_a_bad_index() {
+        let mut chunk = ChunkData::<2, 2>::default();
+        chunk[0][1] = 1;
+        chunk[1][1] = 3;
+        let before = chunk;
+        assert_eq!(chunk.apply_palette(&[7, 8]), Err(3));
+        assert_eq!(chunk, before);
+    }
+
+    #[test]
+    fn copy_region_across_a_chunk_boundary() {
+        let mut world = World::<2, 2>::new();
+        world.insert_chunk(Coord(0, 0), numbered());
+        world.insert_chunk(Coord(1, 0), ChunkData::new(5));
+        world.insert_chunk(Coord(0, 1), ChunkData::default());
+        world.take_dirty().for_each(drop);
+        // Columns 1..=2 of row 0 straddle chunks (0, 0) and (1, 0); row -1 is unloaded.
+        world.copy_region(Coord(2, 0), Coord(1, -1), Coord(1, 2));
+        assert_eq!(world.get_block(Coord(1, 2)), Some(0));
+        assert_eq!(world.get_block(Coord(1, 3)), Some(2));
+        assert_eq!(world.get_block(Coord(2, 3)), None);
+        assert_eq!(
+            world.get_chunk(Coord(0, 1)).unwrap().as_array(),
+            &[[0, 0], [0, 2]]
+        );
+        assert_eq!(world.get_chunk(Coord(1, 0)), Some(&ChunkData::new(5)));
+        let mut dirty: Vec<_> = world.take_dirty().collect();
+        dirty.sort_by_key(|c| (c.1, c.0));
+        assert_eq!(dirty, [Coord(0, 1)]);
+    }
+
+    #[test]
+    fn copy_region_handles_overlap() {
+        let mut world = World::<4, 1>::new();
+        world.insert_chunk(Coord(0, 0), numbered());
+        world.copy_region(Coord(0, 0), Coord(2, 0), Coord(1, 0));
+        assert_eq!(
+            world.get_chunk(Coord(0, 0)).unwrap().as_array(),
+            &[[1, 1, 2, 3]]
+        );
+    }
+
+    #[derive(Serialize, Deserialize, PartialEq, Debug)]
+    struct CompactRow<const N: usize> {
+        #[serde(with = "compact_row")]
+        row: ChunkDataRow<N>,
+    }
+
+    fn seq(values: &[u64]) -> test_value::Value {
+        test_value::Value::Seq(values.iter().map(|&v| test_value::Value::U64(v)).collect())
+    }
+
+    fn compact_row_field(row: &CompactRow<4>) -> test_value::Value {
+        let test_value::Value::Map(fields) = test_value::to_value(row).unwrap() else {
+            panic!("expected a struct");
+        };
+        fields.into_iter().next().unwrap().1
+    }
+
+    #[test]
+    fn compact_row_writes_uniform_rows_as_a_pair() {
+        let row = CompactRow {
+            row: ChunkDataRow::<4>::new(7),
+        };
+        assert_eq!(compact_row_field(&row), seq(&[7, 4]));
+        let back: CompactRow<4> =
+            test_value::from_value(test_value::to_value(&row).unwrap()).unwrap();
+        assert_eq!(back, row);
+    }
+
+    #[test]
+    fn compact_row_writes_varied_rows_in_full() {
+        let mut row = CompactRow {
+            row: ChunkDataRow::<4>::new(7),
+        };
+        row.row[2] = 1;
+        assert_eq!(compact_row_field(&row), seq(&[7, 7, 1, 7]));
+        let back: CompactRow<4> =
+            test_value::from_value(test_value::to_value(&row).unwrap()).unwrap();
+        assert_eq!(back, row);
+
+        let short = CompactRow {
+            row: ChunkDataRow::<2>::new(3),
+        };
+        let back: CompactRow<2> =
+            test_value::from_value(test_value::to_value(&short).unwrap()).unwrap();
+        assert_eq!(back, short);
+    }
+
+    #[test]
+    fn compact_row_rejects_bad_lengths() {
+        let decode =
+            |v: test_value::Value| compact_row::deserialize::<_, 4>(v).map_err(|e| e.to_string());
+        assert_eq!(decode(seq(&[5, 4])), Ok(ChunkDataRow::new(5)));
+        assert!(decode(seq(&[5, 3])).is_err());
+        assert!(decode(seq(&[5, 5, 5])).is_err());
+        assert!(decode(seq(&[5, 5, 5, 5, 5])).is_err());
+    }
+
+    #[test]
+    fn walkable_neighbors_skip_walls_and_unloaded_cells() {
+        let mut world = World::<3, 3>::new();
+        world.insert_chunk(Coord(0, 0), ChunkData::default());
+        world.set_block(Coord(1, 0), 1);
+        let walkable = |block| block == 0;
+        assert_eq!(
+            world.walkable_neighbors(Coord(1, 1), walkable),
+            [Coord(2, 1), Coord(1, 2), Coord(0, 1)]
+        );
+        // (3, 1) is in chunk (1, 0), which isn't loaded yet.
+        assert_eq!(
+            world.walkable_neighbors(Coord(2, 1), walkable),
+            [Coord(2, 0), Coord(2, 2), Coord(1, 1)]
+        );
+        world.insert_chunk(Coord(1, 0), ChunkData::default());
+        assert_eq!(
+            world.walkable_neighbors(Coord(2, 1), walkable),
+            [Coord(2, 0), Coord(3, 1), Coord(2, 2), Coord(1, 1)]
+        );
+    }
+
+    #[test]
+    fn mirror_from_nw_on_a_square_chunk() {
+        let mut chunk = ChunkData::<4, 4>::default();
+        chunk[0][0] = 1;
+        chunk[0][1] = 2;
+        chunk[1][0] = 3;
+        chunk[1][1] = 4;
+        chunk[3][3] = 9;
+        chunk.mirror_from_nw();
+        assert_eq!(
+            chunk.as_array(),
+            &[[1, 2, 2, 1], [3, 4, 4, 3], [3, 4, 4, 3], [1, 2, 2, 1]]
+        );
+    }
+
+    #[test]
+    fn extend_skips_out_of_bounds_pairs() {
+        let mut chunk = ChunkData::<2, 2>::default();
+        chunk.extend(vec![
+            (Coord(1, 0), 4),
+            (Coord(2, 0), 5),
+            (Coord(-1, 1), 6),
+            (Coord(0, 1), 7),
+            (Coord(0, 2), 8),
+        ]);
+        assert_eq!(chunk.as_array(), &[[0, 4], [7, 0]]);
+    }
+
+    #[test]
+    fn chunk_entry_inserts_missing_chunks() {
+        let mut world = World::<2, 2>::new();
+        let mut calls = 0;
+        world.chunk_entry(Coord(1, -1)).or_insert_with(|| {
+            calls += 1;
+            ChunkData::new(3)
+        })[0][1] = 4;
+        world
+            .chunk_entry(Coord(1, -1))
+            .or_insert_with(|| unreachable!("the chunk is already loaded"));
+        assert_eq!(calls, 1);
+        assert_eq!(
+            world.get_chunk(Coord(1, -1)).unwrap().as_array(),
+            &[[3, 4], [3, 3]]
+        );
+        assert!(world.is_dirty(Coord(1, -1)));
+    }
+
+    #[test]
+    fn chunk_entry_modifies_existing_chunks() {
+        let mut world = World::<2, 2>::new();
+        world.insert_chunk(Coord(0, 0), ChunkData::new(1));
+        let shared = world.clone();
+        world.take_dirty().for_each(drop);
+        world
+            .chunk_entry(Coord(0, 0))
+            .and_modify(|chunk| chunk[1][1] = 2)
+            .or_insert(ChunkData::default());
+        assert_eq!(
+            world.get_chunk(Coord(0, 0)).unwrap().as_array(),
+            &[[1, 1], [1, 2]]
+        );
+        assert_eq!(shared.get_chunk(Coord(0, 0)), Some(&ChunkData::new(1)));
+        assert!(world.is_dirty(Coord(0, 0)));
+
+        world
+            .chunk_entry(Coord(5, 5))
+            .and_modify(|_| unreachable!());
+        assert!(world.get_chunk(Coord(5, 5)).is_none());
+        assert!(!world.is_dirty(Coord(5, 5)));
+    }
+
+    #[test]
+    fn block_and_neighbor_across_a_seam() {
+        let mut world = World::<2, 2>::new();
+        world.insert_chunk(Coord(0, 0), numbered());
+        world.insert_chunk(Coord(1, 0), ChunkData::new(9));
+        assert_eq!(
+            world.block_and_neighbor(Coord(1, 1), Direction::East),
+            (Some(4), Some(9))
+        );
+        assert_eq!(
+            world.block_and_neighbor(Coord(2, 0), Direction::West),
+            (Some(9), Some(2))
+        );
+        assert_eq!(
+            world.block_and_neighbor(Coord(0, 0), Direction::South),
+            (Some(1), Some(3))
+        );
+    }
+
+    #[test]
+    fn block_and_neighbor_with_an_unloaded_neighbor() {
+        let mut world = World::<2, 2>::new();
+        world.insert_chunk(Coord(0, 0), numbered());
+        assert_eq!(
+            world.block_and_neighbor(Coord(0, 0), Direction::North),
+            (Some(1), None)
+        );
+        assert_eq!(
+            world.block_and_neighbor(Coord(-1, 0), Direction::East),
+            (None, Some(1))
+        );
+    }
+
+    #[test]
+    fn spiral_starts_at_the_center_and_grows_by_rings() {
+        let center = Coord(3, -2);
+        let cells: Vec<_> = center.spiral(2).collect();
+        assert_eq!(cells[0], center);
+        assert_eq!(cells.len(), 25);
+        let ring1: HashSet<_> = cells[1..9].iter().copied().collect();
+        assert_eq!(ring1, center.neighbors8().iter().copied().collect());
+        assert!(cells[1..9]
+            .iter()
+            .all(|c| c.chebyshev_distance(center) == 1));
+        assert!(cells[9..].iter().all(|c| c.chebyshev_distance(center) == 2));
+        assert_eq!(cells.iter().collect::<HashSet<_>>().len(), cells.len());
+    }
+
+    #[test]
+    fn count_transitions_of_a_uniform_chunk_is_zero() {
+        assert_eq!(ChunkData::<3, 2>::new(4).count_transitions(), 0);
+    }
+
+    #[test]
+    fn count_transitions_of_a_pattern() {
+        // 1 1 2
+        // 1 2 2
+        // Horizontal: 1|2 in both rows. Vertical: only the middle column differs.
+        let mut chunk = ChunkData::<3, 2>::new(1);
+        chunk[0][2] = 2;
+        chunk[1][1] = 2;
+        chunk[1][2] = 2;
+        assert_eq!(chunk.count_transitions(), 3);
+    }
+
+    #[test]
+    fn ensure_loaded_generates_only_missing_chunks() {
+        let mut world = World::<2, 2>::new();
+        world.insert_chunk(Coord(0, 0), ChunkData::new(1));
+        world.take_dirty().for_each(drop);
+        let mut generated = Vec::new();
+        let created =
+            world.ensure_loaded(&[Coord(1, 0), Coord(0, 0), Coord(1, 0), Coord(0, 3)], |c| {
+                generated.push(c);
+                ChunkData::new(2)
+            });
+        assert_eq!(created, [Coord(1, 0), Coord(0, 3)]);
+        assert_eq!(generated, created);
+        assert_eq!(world.len(), 3);
+        assert_eq!(world.get_chunk(Coord(0, 0)), Some(&ChunkData::new(1)));
+        assert_eq!(world.get_chunk(Coord(0, 3)), Some(&ChunkData::new(2)));
+        let mut dirty: Vec<_> = world.take_dirty().collect();
+        dirty.sort_by_key(|c| (c.1, c.0));
+        assert_eq!(dirty, created);
+    }
+
+    #[test]
+    fn fold_cells_sees_every_coordinate() {
+        // Sum of (x + 10 * y) * block over a 3x2 chunk numbered 1..=6.
+        let chunk = numbered::<3, 2>();
+        let sum = chunk.fold_cells(0, |acc, c, block| acc + (c.0 + 10 * c.1) as usize * block);
+        assert_eq!(sum, 2 + 2 * 3 + 10 * 4 + 11 * 5 + 12 * 6);
+    }
+
+    #[test]
+    fn parse_pair_accepts_each_form() {
+        assert_eq!(Coord::parse_pair("3,-4"), Some(Coord(3, -4)));
+        assert_eq!(Coord::parse_pair(" 3 , -4 "), Some(Coord(3, -4)));
+        assert_eq!(Coord::parse_pair("3 -4"), Some(Coord(3, -4)));
+        assert_eq!(Coord::parse_pair("  3\t-4\n"), Some(Coord(3, -4)));
+        assert_eq!(Coord::parse_pair("(3, -4)"), Some(Coord(3, -4)));
+        assert_eq!(Coord::parse_pair(" (3 -4) "), Some(Coord(3, -4)));
+    }
+
+    #[test]
+    fn parse_pair_rejects_malformed_input() {
+        for input in [
+            "", "3", "3,", ",4", "3 4 5", "3,4,5", "(3, 4", "3, 4)", "a,b", "3.5,4",
+        ] {
+            assert_eq!(Coord::parse_pair(input), None, "{:?}", input);
+        }
+    }
+
+    #[test]
+    fn block_counts_by_chunk_omits_chunks_without_the_block() {
+        let mut world = World::<2, 2>::new();
+        world.insert_chunk(Coord(0, 0), ChunkData::new(3));
+        world.insert_chunk(Coord(-1, 0), ChunkData::default());
+        world.insert_chunk(Coord(0, 1), numbered());
+        let counts = world.block_counts_by_chunk(3);
+        assert_eq!(counts.len(), 2);
+        assert_eq!(counts[&Coord(0, 0)], 4);
+        assert_eq!(counts[&Coord(0, 1)], 1);
+        assert!(world.block_counts_by_chunk(7).is_empty());
+    }
+
+    #[test]
+    fn write_to_and_read_streaming_round_trip() {
+        let chunk = numbered::<3, 2>();
+        let mut buf = Vec::new();
+        chunk.write_to(&mut buf).unwrap();
+        assert_eq!(buf.len(), 8 * (1 + 3 * 2));
+        let mut cursor = io::Cursor::new(buf);
+        assert_eq!(
+            ChunkData::<3, 2>::read_streaming(&mut cursor).unwrap(),
+            chunk
+        );
+        assert_eq!(cursor.position(), cursor.get_ref().len() as u64);
+
+        cursor.set_position(0);
+        let err = ChunkData::<2, 2>::read_streaming(&mut cursor).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        let mut truncated = io::Cursor::new(&cursor.get_ref()[..20]);
+        let err = ChunkData::<3, 2>::read_streaming(&mut truncated).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+    }
+
+    #[test]
+    fn to_direction_inverts_offset() {
+        for direction in [
+            Direction::North,
+            Direction::East,
+            Direction::South,
+            Direction::West,
+        ] {
+            assert_eq!(direction.offset().to_direction(), Some(direction));
+        }
+        assert_eq!(Coord(1, 1).to_direction(), None);
+        assert_eq!(Coord(0, 0).to_direction(), None);
+        assert_eq!(Coord(0, -2).to_direction(), None);
+    }
+
+    #[test]
+    fn windows_of_a_3x3_chunk() {
+        let chunk = numbered::<3, 3>();
+        let windows: Vec<_> = chunk.windows::<2>().collect();
+        assert_eq!(windows.len(), 4);
+        assert_eq!(windows[0], (Coord(0, 0), [[1, 2], [4, 5]]));
+        assert_eq!(windows[1], (Coord(1, 0), [[2, 3], [5, 6]]));
+        assert_eq!(windows[2], (Coord(0, 1), [[4, 5], [7, 8]]));
+        assert_eq!(windows[3], (Coord(1, 1), [[5, 6], [8, 9]]));
+        assert_eq!(chunk.windows::<4>().count(), 0);
+    }
+
+    #[test]
+    fn neighbors_8_of_a_3x3_cluster() {
+        let mut world = World::<1, 1>::new();
+        for (i, coord) in Aabb::new(Coord(-1, -1), Coord(1, 1)).iter().enumerate() {
+            world.insert_chunk(coord, ChunkData::new(i));
+        }
+        world.remove_chunk(Coord(-1, 1));
+        let blocks = world
+            .neighbors_8(Coord(0, 0))
+            .map(|chunk| chunk.map(|chunk| chunk[0][0]));
+        // Row-major ids: 0 1 2 / 3 4 5 / 6 7 8, read clockwise from north with 6 removed.
+        assert_eq!(
+            blocks,
+            [
+                Some(1),
+                Some(2),
+                Some(5),
+                Some(8),
+                Some(7),
+                None,
+                Some(3),
+                Some(0)
+            ]
+        );
+        assert_eq!(world.neighbors_8(Coord(5, 5)), [None; 8]);
+    }
+
+    #[test]
+    fn crop_of_an_empty_chunk_is_none() {
+        assert_eq!(ChunkData::<3, 3>::default().crop(), None);
+    }
+
+    #[test]
+    fn crop_keeps_content_in_the_corner() {
+        let mut chunk = ChunkData::<4, 4>::default();
+        chunk[2][3] = 1;
+        chunk[3][2] = 2;
+        assert_eq!(chunk.crop(), Some((Coord(2, 2), 2, 2, vec![0, 1, 2, 0])));
+        let mut corner = ChunkData::<4, 4>::default();
+        corner[0][0] = 6;
+        assert_eq!(corner.crop(), Some((Coord(0, 0), 1, 1, vec![6])));
+    }
+
+    #[test]
+    fn compose_rotation_after_translation() {
+        // Translate by (2, 1), then turn a quarter clockwise: (x, y) -> (-y, x).
+        let t = Affine2::rotation(1).compose(&Affine2::translation(Coord(2, 1)));
+        assert_eq!(
+            t,
+            Affine2 {
+                m: [[0, -1], [1, 0]],
+                t: Coord(-1, 2),
+            }
+        );
+        assert_eq!(t.apply(Coord(3, 0)), Coord(-1, 5));
+        let reversed = Affine2::translation(Coord(2, 1)).compose(&Affine2::rotation(1));
+        assert_eq!(reversed.t, Coord(2, 1));
+        assert_eq!(reversed.apply(Coord(3, 0)), Coord(2, 4));
+    }
+
+    #[test]
+    fn replace_first_changes_only_the_first_match() {
+        let mut chunk = ChunkData::<3, 2>::new(1);
+        chunk[0][1] = 4;
+        chunk[1][0] = 4;
+        assert_eq!(chunk.replace_first(4, 9), Some(Coord(1, 0)));
+        assert_eq!(chunk.as_array(), &[[1, 9, 1], [4, 1, 1]]);
+        assert_eq!(chunk.replace_first(4, 9), Some(Coord(0, 1)));
+    }
+
+    #[test]
+    fn replace_first_of_an_absent_value() {
+        let mut chunk = numbered::<3, 2>();
+        assert_eq!(chunk.replace_first(7, 0), None);
+        assert_eq!(chunk, numbered());
+    }
+
+    #[test]
+    fn toward_steps_in_all_eight_directions() {
+        let from = Coord(5, -5);
+        for dy in -1..=1 {
+            for dx in -1..=1 {
+                let target = Coord(from.0 + 7 * dx, from.1 + 3 * dy);
+                assert_eq!(from.toward(target), Coord(dx, dy));
+            }
+        }
+        assert_eq!(from.toward(Coord(6, -5)), Coord(1, 0));
+        assert_eq!(from.toward(from), Coord(0, 0));
+    }
+
+    #[test]
+    fn row_access_in_and_out_of_range() {
+        let mut chunk = numbered::<3, 2>();
+        assert_eq!(chunk.row(1), Some(&ChunkDataRow([4, 5, 6])));
+        assert_eq!(chunk.row(2), None);
+        chunk.row_mut(0).unwrap()[2] = 0;
+        assert_eq!(chunk.as_array(), &[[1, 2, 0], [4, 5, 6]]);
+        assert!(chunk.row_mut(2).is_none());
+    }
+
+    #[test]
+    fn retain_chunks_near_the_origin() {
+        let mut world = World::<2, 2>::new();
+        world.generate_region(Coord(-3, -3), Coord(3, 3), |_| ChunkData::default());
+        world.retain_chunks(|coord, _| coord.chebyshev_distance(Coord(0, 0)) <= 1);
+        assert_eq!(world.len(), 9);
+        assert_eq!(world.dirty_len(), 9);
+        assert!(world.get_chunk(Coord(1, -1)).is_some());
+        assert!(world.get_chunk(Coord(2, 0)).is_none());
+        assert!(!world.is_dirty(Coord(2, 0)));
+    }
+
+    #[test]
+    fn pack_bits_round_trip_with_a_partial_last_byte() {
+        let mut chunk = ChunkData::<5, 3>::new(2);
+        chunk[0][0] = 7;
+        chunk[1][3] = 7;
+        chunk[2][4] = 7;
+        let bits = chunk.pack_bits(|block| block == 7);
+        // 15 cells need 2 bytes; cells 0, 8 and 14 are set.
+        assert_eq!(bits, [0b0000_0001, 0b0100_0001]);
+        assert_eq!(ChunkData::<5, 3>::from_bits(&bits, 7, 2), Ok(chunk));
+        assert_eq!(
+            ChunkData::<5, 3>::from_bits(&bits[..1], 7, 2),
+            Err(LengthError {
+                expected: 2,
+                actual: 1
+            })
+        );
+    }
+
+    #[test]
+    fn chunk_deserialize_rejects_extra_elements() {
+        use test_value::Value;
+        let Value::Seq(mut rows) = test_value::to_value(&ChunkData::<2, 2>::new(1)).unwrap() else {
+            panic!("expected a sequence of rows");
+        };
+        let mut long_rows = rows.clone();
+        long_rows.push(rows[0].clone());
+        let err = test_value::from_value::<ChunkData<2, 2>>(Value::Seq(long_rows)).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "invalid length 3, expected an array of length 2"
+        );
+
+        let Value::Seq(cells) = &mut rows[1] else {
+            panic!("expected a sequence of cells");
+        };
+        cells.push(Value::U64(1));
+        let err = test_value::from_value::<ChunkData<2, 2>>(Value::Seq(rows)).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "invalid length 3, expected an array of length 2"
+        );
+    }
+
+    #[test]
+    fn chunk_deserialize_rejects_missing_elements() {
+        use test_value::Value;
+        let row = Value::Seq(vec![Value::U64(1), Value::U64(2)]);
+        let err = test_value::from_value::<ChunkData<2, 2>>(Value::Seq(vec![row])).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "invalid length 1, expected an array of length 2"
+        );
+    }
+
+    #[test]
+    fn bounds_checked_cell_access() {
+        let mut chunk = ChunkData::<3, 2>::default();
+        assert_eq!(chunk.set(2, 1, 5), Ok(()));
+        assert_eq!(chunk.get(2, 1), Some(&5));
+        *chunk.get_mut(0, 0).unwrap() = 4;
+        assert_eq!(chunk[0][0], 4);
+
+        assert_eq!(chunk.get(3, 0), None);
+        assert_eq!(chunk.get(0, 2), None);
+        assert_eq!(chunk.get_mut(3, 1), None);
+        assert_eq!(
+            chunk.set(3, 0, 1),
+            Err(ChunkError::OutOfBounds { x: 3, y: 0 })
+        );
+        assert_eq!(
+            chunk.set(0, 2, 1),
+            Err(ChunkError::OutOfBounds { x: 0, y: 2 })
+        );
+        assert_eq!(
+            ChunkError::OutOfBounds { x: 0, y: 2 }.to_string(),
+            "cell (0, 2) is out of bounds"
+        );
+        assert_eq!(chunk.as_array(), &[[4, 0, 0], [0, 0, 5]]);
+    }
+
+    #[test]
+    fn as_array_mut_writes_are_visible_through_get() {
+        let mut chunk = ChunkData::<3, 2>::new(1);
+        chunk.as_array_mut()[1][2] = 9;
+        chunk.as_array_mut()[0] = [4, 5, 6];
+        assert_eq!(chunk.get(2, 1), Some(&9));
+        assert_eq!(chunk.get(1, 0), Some(&5));
+        assert_eq!(chunk.as_array(), &[[4, 5, 6], [1, 1, 9]]);
+    }
+}
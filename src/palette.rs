@@ -0,0 +1,254 @@
+use std::{collections::HashMap, convert::TryFrom, error, fmt};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{BlockID, ChunkData};
+
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum PaletteError {
+    EmptyPalette,
+    WrongBitWidth { expected: u8, actual: u8 },
+    WrongDataLength { expected: usize, actual: usize },
+    IndexOutOfRange(usize),
+}
+
+impl fmt::Display for PaletteError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PaletteError::EmptyPalette => write!(f, "palette is empty"),
+            PaletteError::WrongBitWidth { expected, actual } => {
+                write!(f, "expected {} bits per cell, got {}", expected, actual)
+            }
+            PaletteError::WrongDataLength { expected, actual } => {
+                write!(f, "expected {} data bytes, got {}", expected, actual)
+            }
+            PaletteError::IndexOutOfRange(index) => {
+                write!(f, "palette index {} out of range", index)
+            }
+        }
+    }
+}
+
+impl error::Error for PaletteError {}
+
+#[derive(Deserialize)]
+struct RawPalettedChunk {
+    palette: Vec<BlockID>,
+    bits: u8,
+    data: Vec<u8>,
+}
+
+/// A chunk stored as its distinct blocks plus a palette index per cell, packed into the fewest
+/// bits that can address the palette.
+///
+/// Cells are in row-major order, and index `i` occupies bits `i * bits..(i + 1) * bits` of
+/// `data`, least significant bit first. A single-block chunk needs no data at all, and a
+/// chunk with no cells has an empty palette.
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq, Debug)]
+#[serde(try_from = "RawPalettedChunk")]
+pub struct PalettedChunk<const X: usize, const Y: usize> {
+    palette: Vec<BlockID>,
+    bits: u8,
+    data: Vec<u8>,
+}
+
+fn bits_for(palette_len: usize) -> u8 {
+    if palette_len <= 1 {
+        0
+    } else {
+        (usize::BITS - (palette_len - 1).leading_zeros()) as u8
+    }
+}
+
+fn data_len(cells: usize, bits: u8) -> usize {
+    (cells * bits as usize).div_ceil(8)
+}
+
+impl<const X: usize, const Y: usize> PalettedChunk<X, Y> {
+    pub fn from_chunk(chunk: &ChunkData<X, Y>) -> Self {
+        let mut palette = Vec::new();
+        let mut lookup = HashMap::new();
+        let indices: Vec<usize> = chunk
+            .as_array()
+            .iter()
+            .flatten()
+            .map(|&block| {
+                *lookup.entry(block).or_insert_with(|| {
+                    palette.push(block);
+                    palette.len() - 1
+                })
+            })
+            .collect();
+        let bits = bits_for(palette.len());
+        let mut data = vec![0; data_len(X * Y, bits)];
+        for (i, index) in indices.into_iter().enumerate() {
+            for bit in 0..bits as usize {
+                if index & (1 << bit) != 0 {
+                    let pos = i * bits as usize + bit;
+                    data[pos / 8] |= 1 << (pos % 8);
+                }
+            }
+        }
+        Self {
+            palette,
+            bits,
+            data,
+        }
+    }
+
+    pub fn palette(&self) -> &[BlockID] {
+        &self.palette
+    }
+
+    pub fn bits_per_cell(&self) -> u8 {
+        self.bits
+    }
+
+    fn index(&self, i: usize) -> usize {
+        (0..self.bits as usize).fold(0, |index, bit| {
+            let pos = i * self.bits as usize + bit;
+            index | (((self.data[pos / 8] >> (pos % 8)) & 1) as usize) << bit
+        })
+    }
+
+    pub fn to_chunk(&self) -> ChunkData<X, Y> {
+        let mut chunk = ChunkData::default();
+        for (i, cell) in chunk.as_array_mut().iter_mut().flatten().enumerate() {
+            *cell = self.palette[self.index(i)];
+        }
+        chunk
+    }
+}
+
+impl<const X: usize, const Y: usize> TryFrom<RawPalettedChunk> for PalettedChunk<X, Y> {
+    type Error = PaletteError;
+    fn try_from(raw: RawPalettedChunk) -> Result<Self, Self::Error> {
+        if raw.palette.is_empty() && X * Y > 0 {
+            return Err(PaletteError::EmptyPalette);
+        }
+        let expected = bits_for(raw.palette.len());
+        if raw.bits != expected {
+            return Err(PaletteError::WrongBitWidth {
+                expected,
+                actual: raw.bits,
+            });
+        }
+        let expected = data_len(X * Y, raw.bits);
+        if raw.data.len() != expected {
+            return Err(PaletteError::WrongDataLength {
+                expected,
+                actual: raw.data.len(),
+            });
+        }
+        let chunk = Self {
+            palette: raw.palette,
+            bits: raw.bits,
+            data: raw.data,
+        };
+        if let Some(bad) = (0..X * Y)
+            .map(|i| chunk.index(i))
+            .find(|&index| index >= chunk.palette.len())
+        {
+            return Err(PaletteError::IndexOutOfRange(bad));
+        }
+        Ok(chunk)
+    }
+}
+
+impl<const X: usize, const Y: usize> From<&ChunkData<X, Y>> for PalettedChunk<X, Y> {
+    fn from(chunk: &ChunkData<X, Y>) -> Self {
+        Self::from_chunk(chunk)
+    }
+}
+
+impl<const X: usize, const Y: usize> From<&PalettedChunk<X, Y>> for ChunkData<X, Y> {
+    fn from(paletted: &PalettedChunk<X, Y>) -> Self {
+        paletted.to_chunk()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_value;
+
+    fn distinct<const N: usize>() -> ChunkData<N, 1> {
+        let mut chunk = ChunkData::default();
+        for (i, (_, cell)) in chunk.iter_mut().enumerate() {
+            *cell = 10 * i;
+        }
+        chunk
+    }
+
+    fn raw(palette: Vec<BlockID>, bits: u8, data: Vec<u8>) -> RawPalettedChunk {
+        RawPalettedChunk {
+            palette,
+            bits,
+            data,
+        }
+    }
+
+    #[test]
+    fn round_trip() {
+        let mut chunk = ChunkData::<3, 2>::new(4);
+        chunk[0][1] = 9;
+        chunk[1][2] = 2;
+        let paletted = chunk.to_paletted();
+        assert_eq!(paletted.palette(), [4, 9, 2]);
+        assert_eq!(paletted.to_chunk(), chunk);
+        let back: PalettedChunk<3, 2> =
+            test_value::from_value(test_value::to_value(&paletted).unwrap()).unwrap();
+        assert_eq!(back, paletted);
+    }
+
+    #[test]
+    fn zero_cell_chunks_round_trip() {
+        let paletted = ChunkData::<0, 3>::default().to_paletted();
+        assert!(paletted.palette().is_empty());
+        let back: PalettedChunk<0, 3> =
+            test_value::from_value(test_value::to_value(&paletted).unwrap()).unwrap();
+        assert_eq!(back, paletted);
+    }
+
+    #[test]
+    fn bits_per_cell_at_palette_size_boundaries() {
+        assert_eq!(distinct::<1>().to_paletted().bits_per_cell(), 0);
+        assert_eq!(distinct::<2>().to_paletted().bits_per_cell(), 1);
+        assert_eq!(distinct::<3>().to_paletted().bits_per_cell(), 2);
+        assert_eq!(distinct::<4>().to_paletted().bits_per_cell(), 2);
+        assert_eq!(distinct::<16>().to_paletted().bits_per_cell(), 4);
+        let paletted = distinct::<17>().to_paletted();
+        assert_eq!(paletted.bits_per_cell(), 5);
+        assert_eq!(paletted.to_chunk(), distinct::<17>());
+        assert!(ChunkData::<4, 4>::new(3).to_paletted().data.is_empty());
+    }
+
+    #[test]
+    fn try_from_rejects_inconsistent_parts() {
+        type Chunk = PalettedChunk<2, 2>;
+        assert_eq!(
+            Chunk::try_from(raw(vec![], 0, vec![])),
+            Err(PaletteError::EmptyPalette)
+        );
+        assert_eq!(
+            Chunk::try_from(raw(vec![1, 2], 2, vec![0])),
+            Err(PaletteError::WrongBitWidth {
+                expected: 1,
+                actual: 2
+            })
+        );
+        assert_eq!(
+            Chunk::try_from(raw(vec![1, 2], 1, vec![0, 0])),
+            Err(PaletteError::WrongDataLength {
+                expected: 1,
+                actual: 2
+            })
+        );
+        // Two bits per cell address up to four entries, but the palette only has three.
+        assert_eq!(
+            Chunk::try_from(raw(vec![1, 2, 3], 2, vec![0b1100_0000])),
+            Err(PaletteError::IndexOutOfRange(3))
+        );
+        assert!(Chunk::try_from(raw(vec![1, 2, 3], 2, vec![0b1001_0000])).is_ok());
+    }
+}
@@ -0,0 +1,383 @@
+//! Region files grouping `REGION_SIZE x REGION_SIZE` chunks for on-disk persistence.
+//!
+//! A region file starts with a 16 byte header:
+//!
+//! | bytes | field                               |
+//! |-------|-------------------------------------|
+//! | 4     | magic `b"VRGN"`                     |
+//! | 2     | format version, little-endian `u16` |
+//! | 2     | `REGION_SIZE`, little-endian `u16`  |
+//! | 4     | chunk width, little-endian `u32`    |
+//! | 4     | chunk height, little-endian `u32`   |
+//!
+//! After the header comes an offset table with one entry per slot in row-major slot order. Each
+//! entry is a little-endian `u64` byte offset and a `u32` length, and a zero length marks an
+//! empty slot. The rest of the file holds the chunk payloads in `ChunkData::encode_rle` format.
+//!
+//! Writes are atomic. The new file is written next to the old one, synced, and then renamed
+//! over it, so a crash leaves either the old region or the new one.
+
+use std::{
+    convert::TryFrom,
+    fs::{self, File},
+    io::{self, Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+};
+
+use crate::{ChunkData, Coord};
+
+pub const REGION_SIZE: usize = 32;
+
+const MAGIC: &[u8; 4] = b"VRGN";
+const VERSION: u16 = 1;
+const HEADER_LEN: usize = 16;
+const ENTRY_LEN: usize = 12;
+const SLOTS: usize = REGION_SIZE * REGION_SIZE;
+
+fn invalid_data(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg.to_string())
+}
+
+/// Splits a chunk coordinate into the coordinate of its region and its slot within it.
+pub fn region_of(chunk: Coord) -> (Coord, Coord) {
+    let size = REGION_SIZE as isize;
+    (
+        Coord(chunk.0.div_euclid(size), chunk.1.div_euclid(size)),
+        Coord(chunk.0.rem_euclid(size), chunk.1.rem_euclid(size)),
+    )
+}
+
+fn slot_index(slot: Coord) -> io::Result<usize> {
+    match slot.to_usize() {
+        Some((x, y)) if x < REGION_SIZE && y < REGION_SIZE => Ok(y * REGION_SIZE + x),
+        _ => Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("slot {} is outside the region", slot),
+        )),
+    }
+}
+
+/// One region file holding chunks of size `X x Y`, addressed by slot coordinates in
+/// `0..REGION_SIZE`.
+pub struct RegionFile<const X: usize, const Y: usize> {
+    path: PathBuf,
+    table: Vec<Option<(u64, u32)>>,
+}
+
+impl<const X: usize, const Y: usize> RegionFile<X, Y> {
+    /// Opens the region at `path`, creating an empty one if the file does not exist.
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let mut region = Self {
+            path,
+            table: vec![None; SLOTS],
+        };
+        match File::open(&region.path) {
+            Ok(mut file) => region.read_table(&mut file)?,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                region.write_atomic(&vec![None; SLOTS])?
+            }
+            Err(e) => return Err(e),
+        }
+        Ok(region)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    fn read_table(&mut self, file: &mut File) -> io::Result<()> {
+        let file_len = file.metadata()?.len();
+        let mut header = [0; HEADER_LEN];
+        file.read_exact(&mut header)?;
+        if &header[..4] != MAGIC {
+            return Err(invalid_data("not a region file"));
+        }
+        let version = u16::from_le_bytes([header[4], header[5]]);
+        if version != VERSION {
+            return Err(invalid_data(&format!(
+                "unsupported region version {}",
+                version
+            )));
+        }
+        let size = u16::from_le_bytes([header[6], header[7]]) as usize;
+        let width = u32::from_le_bytes([header[8], header[9], header[10], header[11]]) as usize;
+        let height = u32::from_le_bytes([header[12], header[13], header[14], header[15]]) as usize;
+        if size != REGION_SIZE || width != X || height != Y {
+            return Err(invalid_data("region dimensions do not match"));
+        }
+        let mut table = vec![0; SLOTS * ENTRY_LEN];
+        file.read_exact(&mut table)?;
+        for (slot, entry) in self.table.iter_mut().zip(table.chunks_exact(ENTRY_LEN)) {
+            let offset = u64::from_le_bytes(<[u8; 8]>::try_from(&entry[..8]).unwrap());
+            let len = u32::from_le_bytes(<[u8; 4]>::try_from(&entry[8..]).unwrap());
+            if len == 0 {
+                *slot = None;
+            } else if offset
+                .checked_add(len as u64)
+                .is_none_or(|end| end > file_len)
+            {
+                return Err(invalid_data(
+                    "region offset table points past the end of the file",
+                ));
+            } else {
+                *slot = Some((offset, len));
+            }
+        }
+        Ok(())
+    }
+
+    fn read_payload(&self, file: &mut File, index: usize) -> io::Result<Option<Vec<u8>>> {
+        let (offset, len) = match self.table[index] {
+            Some(entry) => entry,
+            None => return Ok(None),
+        };
+        let mut payload = vec![0; len as usize];
+        file.seek(SeekFrom::Start(offset))?;
+        file.read_exact(&mut payload)?;
+        Ok(Some(payload))
+    }
+
+    pub fn contains(&self, slot: Coord) -> io::Result<bool> {
+        Ok(self.table[slot_index(slot)?].is_some())
+    }
+
+    pub fn read_chunk(&self, slot: Coord) -> io::Result<Option<ChunkData<X, Y>>> {
+        let index = slot_index(slot)?;
+        if self.table[index].is_none() {
+            return Ok(None);
+        }
+        let payload = self.read_payload(&mut File::open(&self.path)?, index)?;
+        payload
+            .map(|bytes| {
+                ChunkData::decode_rle(&bytes)
+                    .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+            })
+            .transpose()
+    }
+
+    /// Stores `chunk` in `slot`, atomically rewriting this region file (but no other).
+    pub fn write_chunk(&mut self, slot: Coord, chunk: &ChunkData<X, Y>) -> io::Result<()> {
+        self.update(slot_index(slot)?, Some(chunk.encode_rle()))
+    }
+
+    pub fn remove_chunk(&mut self, slot: Coord) -> io::Result<()> {
+        self.update(slot_index(slot)?, None)
+    }
+
+    fn update(&mut self, index: usize, payload: Option<Vec<u8>>) -> io::Result<()> {
+        let mut file = File::open(&self.path)?;
+        let mut payloads = (0..SLOTS)
+            .map(|i| self.read_payload(&mut file, i))
+            .collect::<io::Result<Vec<_>>>()?;
+        payloads[index] = payload;
+        self.write_atomic(&payloads)
+    }
+
+    fn write_atomic(&mut self, payloads: &[Option<Vec<u8>>]) -> io::Result<()> {
+        let mut out = Vec::with_capacity(HEADER_LEN + SLOTS * ENTRY_LEN);
+        out.extend_from_slice(MAGIC);
+        out.extend_from_slice(&VERSION.to_le_bytes());
+        out.extend_from_slice(&(REGION_SIZE as u16).to_le_bytes());
+        out.extend_from_slice(&(X as u32).to_le_bytes());
+        out.extend_from_slice(&(Y as u32).to_le_bytes());
+        let mut table = vec![None; SLOTS];
+        let mut offset = (HEADER_LEN + SLOTS * ENTRY_LEN) as u64;
+        for (entry, payload) in table.iter_mut().zip(payloads) {
+            let len = payload.as_ref().map_or(0, |p| p.len() as u32);
+            out.extend_from_slice(&offset.to_le_bytes());
+            out.extend_from_slice(&len.to_le_bytes());
+            if len > 0 {
+                *entry = Some((offset, len));
+                offset += len as u64;
+            }
+        }
+        for payload in payloads.iter().flatten() {
+            out.extend_from_slice(payload);
+        }
+
+        let mut tmp_name = self.path.file_name().unwrap_or_default().to_os_string();
+        tmp_name.push(".tmp");
+        let tmp = self.path.with_file_name(tmp_name);
+        let mut file = File::create(&tmp)?;
+        file.write_all(&out)?;
+        file.sync_all()?;
+        fs::rename(&tmp, &self.path)?;
+        self.table = table;
+        Ok(())
+    }
+}
+
+/// A directory of region files, addressed by world chunk coordinate.
+pub struct RegionStore<const X: usize, const Y: usize> {
+    dir: PathBuf,
+}
+
+impl<const X: usize, const Y: usize> RegionStore<X, Y> {
+    pub fn new<P: AsRef<Path>>(dir: P) -> io::Result<Self> {
+        fs::create_dir_all(&dir)?;
+        Ok(Self {
+            dir: dir.as_ref().to_path_buf(),
+        })
+    }
+
+    fn region_path(&self, region: Coord) -> PathBuf {
+        self.dir.join(format!("r.{}.{}.region", region.0, region.1))
+    }
+
+    pub fn read_chunk(&self, chunk: Coord) -> io::Result<Option<ChunkData<X, Y>>> {
+        let (region, slot) = region_of(chunk);
+        let path = self.region_path(region);
+        if !path.exists() {
+            return Ok(None);
+        }
+        RegionFile::<X, Y>::open(path)?.read_chunk(slot)
+    }
+
+    pub fn write_chunk(&self, chunk: Coord, data: &ChunkData<X, Y>) -> io::Result<()> {
+        let (region, slot) = region_of(chunk);
+        RegionFile::open(self.region_path(region))?.write_chunk(slot, data)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use super::*;
+
+    /// A fresh directory under the system temp dir, removed again on drop.
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new() -> Self {
+            static NEXT: AtomicUsize = AtomicUsize::new(0);
+            let dir = std::env::temp_dir().join(format!(
+                "data_types-region-{}-{}",
+                std::process::id(),
+                NEXT.fetch_add(1, Ordering::Relaxed)
+            ));
+            fs::create_dir_all(&dir).unwrap();
+            Self(dir)
+        }
+
+        fn join(&self, name: &str) -> PathBuf {
+            self.0.join(name)
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    fn numbered() -> ChunkData<4, 2> {
+        let mut chunk = ChunkData::default();
+        for (i, (_, cell)) in chunk.iter_mut().enumerate() {
+            *cell = i + 1;
+        }
+        chunk
+    }
+
+    fn patch(path: &Path, at: usize, bytes: &[u8]) {
+        let mut contents = fs::read(path).unwrap();
+        contents[at..at + bytes.len()].copy_from_slice(bytes);
+        fs::write(path, contents).unwrap();
+    }
+
+    #[test]
+    fn open_creates_an_empty_region() {
+        let dir = TempDir::new();
+        let path = dir.join("r.region");
+        let region = RegionFile::<4, 2>::open(&path).unwrap();
+        assert_eq!(region.path(), path);
+        assert_eq!(
+            fs::metadata(&path).unwrap().len(),
+            (HEADER_LEN + SLOTS * ENTRY_LEN) as u64
+        );
+        assert!(!region.contains(Coord(0, 0)).unwrap());
+        assert!(RegionFile::<4, 2>::open(&path).is_ok());
+    }
+
+    #[test]
+    fn write_read_and_remove_round_trip() {
+        let dir = TempDir::new();
+        let path = dir.join("r.region");
+        let mut region = RegionFile::<4, 2>::open(&path).unwrap();
+        let (a, b) = (Coord(3, 1), Coord(31, 31));
+        region.write_chunk(a, &numbered()).unwrap();
+        region.write_chunk(b, &ChunkData::new(7)).unwrap();
+        assert_eq!(region.read_chunk(a).unwrap(), Some(numbered()));
+
+        let reopened = RegionFile::<4, 2>::open(&path).unwrap();
+        assert_eq!(reopened.read_chunk(b).unwrap(), Some(ChunkData::new(7)));
+        assert_eq!(reopened.read_chunk(Coord(0, 0)).unwrap(), None);
+
+        region.remove_chunk(a).unwrap();
+        assert!(!region.contains(a).unwrap());
+        let reopened = RegionFile::<4, 2>::open(&path).unwrap();
+        assert_eq!(reopened.read_chunk(a).unwrap(), None);
+        assert_eq!(reopened.read_chunk(b).unwrap(), Some(ChunkData::new(7)));
+
+        let err = region.read_chunk(Coord(32, 0)).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    fn open_rejects_an_offset_past_the_end() {
+        let dir = TempDir::new();
+        let path = dir.join("r.region");
+        RegionFile::<4, 2>::open(&path)
+            .unwrap()
+            .write_chunk(Coord(0, 0), &numbered())
+            .unwrap();
+        patch(&path, HEADER_LEN, &u64::MAX.to_le_bytes());
+        let err = RegionFile::<4, 2>::open(&path).err().unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert!(err.to_string().contains("past the end"));
+    }
+
+    #[test]
+    fn open_rejects_other_versions_and_dimensions() {
+        let dir = TempDir::new();
+        let path = dir.join("r.region");
+        RegionFile::<4, 2>::open(&path).unwrap();
+
+        let err = RegionFile::<2, 4>::open(&path).err().unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert!(err.to_string().contains("dimensions"));
+
+        patch(&path, 4, &(VERSION + 1).to_le_bytes());
+        let err = RegionFile::<4, 2>::open(&path).err().unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert!(err.to_string().contains("version"));
+    }
+
+    #[test]
+    fn saving_leaves_no_tmp_file() {
+        let dir = TempDir::new();
+        let mut region = RegionFile::<4, 2>::open(dir.join("r.region")).unwrap();
+        region.write_chunk(Coord(1, 1), &numbered()).unwrap();
+        let names: Vec<_> = fs::read_dir(&dir.0)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name())
+            .collect();
+        assert_eq!(names, ["r.region"]);
+    }
+
+    #[test]
+    fn store_routes_chunks_to_region_files() {
+        let dir = TempDir::new();
+        let store = RegionStore::<4, 2>::new(dir.join("world")).unwrap();
+        assert_eq!(store.read_chunk(Coord(-1, 40)).unwrap(), None);
+        store.write_chunk(Coord(-1, 40), &numbered()).unwrap();
+        store.write_chunk(Coord(0, 0), &ChunkData::new(2)).unwrap();
+        assert_eq!(store.read_chunk(Coord(-1, 40)).unwrap(), Some(numbered()));
+        assert_eq!(
+            store.read_chunk(Coord(0, 0)).unwrap(),
+            Some(ChunkData::new(2))
+        );
+        assert!(dir.join("world").join("r.-1.1.region").exists());
+    }
+}
@@ -0,0 +1,194 @@
+use std::{collections::HashMap, error, fmt};
+
+use serde::{de::Error, Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::BlockID;
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct BlockDefinition<M> {
+    pub name: String,
+    pub opaque: bool,
+    pub solid: bool,
+    pub metadata: M,
+}
+
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub enum RegistryError {
+    DuplicateName(String),
+    UnknownName(String),
+}
+
+impl fmt::Display for RegistryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RegistryError::DuplicateName(name) => {
+                write!(f, "block {:?} is already registered", name)
+            }
+            RegistryError::UnknownName(name) => write!(f, "block {:?} is not registered", name),
+        }
+    }
+}
+
+impl error::Error for RegistryError {}
+
+/// Block definitions indexed by `BlockID` in registration order, and looked up by unique name.
+///
+/// Ids depend on registration order, so saves should store `names()` next to their chunks. On
+/// load, `remap_from` converts those saved ids to the current ones.
+#[derive(Clone, PartialEq, Debug)]
+pub struct BlockRegistry<M> {
+    blocks: Vec<BlockDefinition<M>>,
+    by_name: HashMap<String, BlockID>,
+}
+
+impl<M> Default for BlockRegistry<M> {
+    fn default() -> Self {
+        Self {
+            blocks: Vec::new(),
+            by_name: HashMap::new(),
+        }
+    }
+}
+
+impl<M> BlockRegistry<M> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.blocks.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.blocks.is_empty()
+    }
+
+    /// Registers `definition` under its name, returning the id assigned to it.
+    pub fn register(&mut self, definition: BlockDefinition<M>) -> Result<BlockID, RegistryError> {
+        if self.by_name.contains_key(&definition.name) {
+            return Err(RegistryError::DuplicateName(definition.name));
+        }
+        let id = self.blocks.len();
+        self.by_name.insert(definition.name.clone(), id);
+        self.blocks.push(definition);
+        Ok(id)
+    }
+
+    pub fn get(&self, id: BlockID) -> Option<&BlockDefinition<M>> {
+        self.blocks.get(id)
+    }
+
+    pub fn get_by_name(&self, name: &str) -> Option<&BlockDefinition<M>> {
+        self.get(self.id_of(name)?)
+    }
+
+    pub fn id_of(&self, name: &str) -> Option<BlockID> {
+        self.by_name.get(name).copied()
+    }
+
+    pub fn name_of(&self, id: BlockID) -> Option<&str> {
+        self.get(id).map(|definition| definition.name.as_str())
+    }
+
+    /// Block names indexed by their current id.
+    pub fn names(&self) -> Vec<String> {
+        self.blocks
+            .iter()
+            .map(|definition| definition.name.clone())
+            .collect()
+    }
+
+    /// Maps ids from a save whose `names()` were `saved` onto this registry's ids. The result
+    /// can be passed straight to `ChunkData::apply_palette`.
+    pub fn remap_from(&self, saved: &[String]) -> Result<Vec<BlockID>, RegistryError> {
+        saved
+            .iter()
+            .map(|name| {
+                self.id_of(name)
+                    .ok_or_else(|| RegistryError::UnknownName(name.clone()))
+            })
+            .collect()
+    }
+}
+
+impl<M: Serialize> Serialize for BlockRegistry<M> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.blocks.serialize(serializer)
+    }
+}
+
+impl<'de, M: Deserialize<'de>> Deserialize<'de> for BlockRegistry<M> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let mut registry = Self::new();
+        for definition in Vec::<BlockDefinition<M>>::deserialize(deserializer)? {
+            registry.register(definition).map_err(D::Error::custom)?;
+        }
+        Ok(registry)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn block(name: &str) -> BlockDefinition<()> {
+        BlockDefinition {
+            name: name.to_string(),
+            opaque: true,
+            solid: true,
+            metadata: (),
+        }
+    }
+
+    #[test]
+    fn ids_follow_registration_order() {
+        let mut registry = BlockRegistry::new();
+        assert_eq!(registry.register(block("air")), Ok(0));
+        assert_eq!(registry.register(block("stone")), Ok(1));
+        assert_eq!(registry.register(block("dirt")), Ok(2));
+        assert_eq!(registry.len(), 3);
+        assert_eq!(registry.names(), ["air", "stone", "dirt"]);
+    }
+
+    #[test]
+    fn lookup_by_name_and_id() {
+        let mut registry = BlockRegistry::new();
+        registry.register(block("air")).unwrap();
+        registry.register(block("stone")).unwrap();
+        assert_eq!(registry.id_of("stone"), Some(1));
+        assert_eq!(registry.name_of(1), Some("stone"));
+        assert_eq!(registry.get(0), Some(&block("air")));
+        assert_eq!(registry.get_by_name("air"), Some(&block("air")));
+        assert_eq!(registry.id_of("water"), None);
+        assert_eq!(registry.get(2), None);
+        assert_eq!(registry.name_of(2), None);
+    }
+
+    #[test]
+    fn duplicate_names_are_rejected() {
+        let mut registry = BlockRegistry::new();
+        registry.register(block("stone")).unwrap();
+        let mut again = block("stone");
+        again.solid = false;
+        assert_eq!(
+            registry.register(again),
+            Err(RegistryError::DuplicateName("stone".to_string()))
+        );
+        assert_eq!(registry.len(), 1);
+        assert!(registry.get(0).unwrap().solid);
+    }
+
+    #[test]
+    fn remap_from_saved_names() {
+        let mut registry = BlockRegistry::new();
+        for name in ["air", "dirt", "stone"] {
+            registry.register(block(name)).unwrap();
+        }
+        let saved = ["stone".to_string(), "air".to_string()];
+        assert_eq!(registry.remap_from(&saved), Ok(vec![2, 0]));
+        assert_eq!(
+            registry.remap_from(&["lava".to_string()]),
+            Err(RegistryError::UnknownName("lava".to_string()))
+        );
+    }
+}
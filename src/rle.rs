@@ -0,0 +1,247 @@
+use std::{convert::TryFrom, error, fmt};
+
+use crate::{BlockID, ChunkData};
+
+const VERSION: u8 = 1;
+
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum DecodeError {
+    Truncated,
+    UnsupportedVersion(u8),
+    WrongDimensions { width: u32, height: u32 },
+    InvalidVarint,
+    InvalidRunLength,
+    TrailingBytes,
+    UnsupportedIdWidth(u8),
+    BlockIdOutOfRange(u64),
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DecodeError::Truncated => write!(f, "unexpected end of input"),
+            DecodeError::UnsupportedVersion(version) => {
+                write!(f, "unsupported format version {}", version)
+            }
+            DecodeError::WrongDimensions { width, height } => {
+                write!(f, "encoded chunk is {}x{}", width, height)
+            }
+            DecodeError::InvalidVarint => write!(f, "malformed or oversized varint"),
+            DecodeError::InvalidRunLength => write!(f, "runs do not cover the chunk exactly"),
+            DecodeError::TrailingBytes => write!(f, "unexpected bytes after the last cell"),
+            DecodeError::UnsupportedIdWidth(width) => {
+                write!(f, "unsupported block id width of {} bytes", width)
+            }
+            DecodeError::BlockIdOutOfRange(id) => {
+                write!(f, "block id {} does not fit in a BlockID", id)
+            }
+        }
+    }
+}
+
+impl error::Error for DecodeError {}
+
+fn write_varint(out: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        out.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+
+fn read_varint(input: &mut &[u8]) -> Result<u64, DecodeError> {
+    let mut value = 0u64;
+    for shift in (0..64).step_by(7) {
+        let (&byte, rest) = input.split_first().ok_or(DecodeError::Truncated)?;
+        *input = rest;
+        let bits = (byte & 0x7f) as u64;
+        if shift == 63 && bits > 1 {
+            return Err(DecodeError::InvalidVarint);
+        }
+        value |= bits << shift;
+        if byte & 0x80 == 0 {
+            return Ok(value);
+        }
+    }
+    Err(DecodeError::InvalidVarint)
+}
+
+pub(crate) fn read_u32(input: &mut &[u8]) -> Result<u32, DecodeError> {
+    if input.len() < 4 {
+        return Err(DecodeError::Truncated);
+    }
+    let (bytes, rest) = input.split_at(4);
+    *input = rest;
+    Ok(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
+
+impl<const X: usize, const Y: usize> ChunkData<X, Y> {
+    /// Encodes the chunk as runs of identical blocks.
+    ///
+    /// Layout, version 1:
+    ///
+    /// | bytes    | field                                              |
+    /// |----------|----------------------------------------------------|
+    /// | 1        | format version, currently `1`                      |
+    /// | 4        | chunk width `X`, little-endian `u32`               |
+    /// | 4        | chunk height `Y`, little-endian `u32`              |
+    /// | variable | runs, each a LEB128 run length then a LEB128 block |
+    ///
+    /// Runs cover the cells in row-major order and may continue across rows. Their lengths are
+    /// never zero and add up to exactly `X * Y`. Nothing may follow the last run.
+    pub fn encode_rle(&self) -> Vec<u8> {
+        let mut out = vec![VERSION];
+        out.extend_from_slice(&(X as u32).to_le_bytes());
+        out.extend_from_slice(&(Y as u32).to_le_bytes());
+        let mut cells = self.as_array().iter().flatten().copied().peekable();
+        while let Some(block) = cells.next() {
+            let mut len = 1u64;
+            while cells.next_if_eq(&block).is_some() {
+                len += 1;
+            }
+            write_varint(&mut out, len);
+            write_varint(&mut out, block as u64);
+        }
+        out
+    }
+
+    pub fn decode_rle(bytes: &[u8]) -> Result<Self, DecodeError> {
+        let mut input = bytes;
+        let (&version, rest) = input.split_first().ok_or(DecodeError::Truncated)?;
+        input = rest;
+        if version != VERSION {
+            return Err(DecodeError::UnsupportedVersion(version));
+        }
+        let (width, height) = (read_u32(&mut input)?, read_u32(&mut input)?);
+        if width as usize != X || height as usize != Y {
+            return Err(DecodeError::WrongDimensions { width, height });
+        }
+        let mut chunk = Self::default();
+        let mut cells = chunk.as_array_mut().iter_mut().flatten();
+        let mut remaining = X * Y;
+        while remaining > 0 {
+            let len = usize::try_from(read_varint(&mut input)?)
+                .map_err(|_| DecodeError::InvalidRunLength)?;
+            if len == 0 || len > remaining {
+                return Err(DecodeError::InvalidRunLength);
+            }
+            let block = BlockID::try_from(read_varint(&mut input)?)
+                .map_err(|_| DecodeError::InvalidVarint)?;
+            for cell in cells.by_ref().take(len) {
+                *cell = block;
+            }
+            remaining -= len;
+        }
+        if !input.is_empty() {
+            return Err(DecodeError::TrailingBytes);
+        }
+        Ok(chunk)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn header(width: u32, height: u32) -> Vec<u8> {
+        let mut out = vec![VERSION];
+        out.extend_from_slice(&width.to_le_bytes());
+        out.extend_from_slice(&height.to_le_bytes());
+        out
+    }
+
+    #[test]
+    fn round_trip() {
+        let mut chunk = ChunkData::<4, 3>::new(2);
+        chunk[0][3] = 300;
+        chunk[1][0] = 300;
+        chunk[2][2] = 0;
+        let bytes = chunk.encode_rle();
+        // Runs: 3x2, 2x300 (across the row break), 5x2, 1x0, 1x2.
+        let mut expected = header(4, 3);
+        expected.extend_from_slice(&[3, 2, 2, 0xac, 0x02, 5, 2, 1, 0, 1, 2]);
+        assert_eq!(bytes, expected);
+        assert_eq!(ChunkData::<4, 3>::decode_rle(&bytes), Ok(chunk));
+    }
+
+    #[test]
+    fn rejects_truncated_input() {
+        let bytes = ChunkData::<2, 2>::new(200).encode_rle();
+        for len in [0, 1, 5, bytes.len() - 1] {
+            assert_eq!(
+                ChunkData::<2, 2>::decode_rle(&bytes[..len]),
+                Err(DecodeError::Truncated)
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_other_versions() {
+        let mut bytes = ChunkData::<2, 2>::default().encode_rle();
+        bytes[0] = 2;
+        assert_eq!(
+            ChunkData::<2, 2>::decode_rle(&bytes),
+            Err(DecodeError::UnsupportedVersion(2))
+        );
+    }
+
+    #[test]
+    fn rejects_other_dimensions() {
+        let bytes = ChunkData::<4, 1>::default().encode_rle();
+        assert_eq!(
+            ChunkData::<2, 2>::decode_rle(&bytes),
+            Err(DecodeError::WrongDimensions {
+                width: 4,
+                height: 1
+            })
+        );
+    }
+
+    #[test]
+    fn rejects_a_zero_length_run() {
+        let mut bytes = header(2, 2);
+        bytes.extend_from_slice(&[0, 1, 4, 1]);
+        assert_eq!(
+            ChunkData::<2, 2>::decode_rle(&bytes),
+            Err(DecodeError::InvalidRunLength)
+        );
+    }
+
+    #[test]
+    fn rejects_a_run_past_the_end_of_the_chunk() {
+        let mut bytes = header(2, 2);
+        bytes.extend_from_slice(&[3, 1, 2, 1]);
+        assert_eq!(
+            ChunkData::<2, 2>::decode_rle(&bytes),
+            Err(DecodeError::InvalidRunLength)
+        );
+    }
+
+    #[test]
+    fn rejects_an_oversized_varint() {
+        let mut bytes = header(2, 2);
+        bytes.extend_from_slice(&[0xff; 9]);
+        bytes.push(0x02);
+        assert_eq!(
+            ChunkData::<2, 2>::decode_rle(&bytes),
+            Err(DecodeError::InvalidVarint)
+        );
+        let mut bytes = header(2, 2);
+        bytes.extend_from_slice(&[4]);
+        bytes.extend_from_slice(&[0x80; 10]);
+        assert_eq!(
+            ChunkData::<2, 2>::decode_rle(&bytes),
+            Err(DecodeError::InvalidVarint)
+        );
+    }
+
+    #[test]
+    fn rejects_trailing_bytes() {
+        let mut bytes = ChunkData::<2, 2>::default().encode_rle();
+        bytes.push(0);
+        assert_eq!(
+            ChunkData::<2, 2>::decode_rle(&bytes),
+            Err(DecodeError::TrailingBytes)
+        );
+    }
+}
@@ -0,0 +1,374 @@
+use std::convert::TryFrom;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{BlockID, ChunkData, ChunkError, Coord, LengthError, World};
+
+/// How `Structure::stamp` treats the structure's air cells, which hold the default block.
+#[derive(PartialEq, Eq, Hash, Clone, Copy, Serialize, Deserialize, Debug)]
+pub enum StampMode {
+    /// Every cell is written, so air clears whatever was there.
+    Overwrite,
+    /// Air cells leave the target untouched.
+    SkipAir,
+}
+
+#[derive(Deserialize)]
+struct RawStructure {
+    width: usize,
+    height: usize,
+    anchor: Coord,
+    blocks: Vec<BlockID>,
+}
+
+/// A `width x height` template of blocks in row-major order, placed so that `anchor` lands on
+/// the target coordinate.
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq, Debug)]
+#[serde(try_from = "RawStructure")]
+pub struct Structure {
+    width: usize,
+    height: usize,
+    anchor: Coord,
+    blocks: Vec<BlockID>,
+}
+
+impl Structure {
+    /// # Panics
+    ///
+    /// Panics if `width * height` overflows `usize`.
+    pub fn new(width: usize, height: usize, block: BlockID) -> Self {
+        let len = width
+            .checked_mul(height)
+            .expect("structure dimensions overflow usize");
+        Self {
+            width,
+            height,
+            anchor: Coord::default(),
+            blocks: vec![block; len],
+        }
+    }
+
+    /// Fails unless `blocks` holds exactly `width * height` cells. When that product overflows
+    /// no list can match, and the error reports `usize::MAX` as the expected length.
+    pub fn from_blocks(
+        width: usize,
+        height: usize,
+        blocks: Vec<BlockID>,
+    ) -> Result<Self, LengthError> {
+        match width.checked_mul(height) {
+            Some(len) if len == blocks.len() => {}
+            len => {
+                return Err(LengthError {
+                    expected: len.unwrap_or(usize::MAX),
+                    actual: blocks.len(),
+                })
+            }
+        }
+        Ok(Self {
+            width,
+            height,
+            anchor: Coord::default(),
+            blocks,
+        })
+    }
+
+    pub fn from_chunk<const X: usize, const Y: usize>(chunk: &ChunkData<X, Y>) -> Self {
+        Self {
+            width: X,
+            height: Y,
+            anchor: Coord::default(),
+            blocks: chunk.as_array().as_flattened().to_vec(),
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn anchor(&self) -> Coord {
+        self.anchor
+    }
+
+    /// The anchor may lie outside the template, e.g. below a tree's trunk.
+    pub fn with_anchor(mut self, anchor: Coord) -> Self {
+        self.anchor = anchor;
+        self
+    }
+
+    pub fn get(&self, x: usize, y: usize) -> Option<BlockID> {
+        if x < self.width && y < self.height {
+            Some(self.blocks[y * self.width + x])
+        } else {
+            None
+        }
+    }
+
+    pub fn set(&mut self, x: usize, y: usize, block: BlockID) -> Result<(), ChunkError> {
+        if x >= self.width || y >= self.height {
+            return Err(ChunkError::OutOfBounds { x, y });
+        }
+        self.blocks[y * self.width + x] = block;
+        Ok(())
+    }
+
+    /// Builds a `width x height` structure where cell `(x, y)` comes from `source(x, y)` of
+    /// `self`, with the anchor moved by `map`.
+    fn remap(
+        &self,
+        width: usize,
+        height: usize,
+        source: impl Fn(usize, usize) -> (usize, usize),
+        map: impl Fn(Coord) -> Coord,
+    ) -> Self {
+        let mut blocks = Vec::with_capacity(width * height);
+        for y in 0..height {
+            for x in 0..width {
+                let (sx, sy) = source(x, y);
+                blocks.push(self.blocks[sy * self.width + sx]);
+            }
+        }
+        Self {
+            width,
+            height,
+            anchor: map(self.anchor),
+            blocks,
+        }
+    }
+
+    /// Rotates 90 degrees clockwise (with `y` pointing south), swapping the dimensions.
+    pub fn rotate_cw(&self) -> Self {
+        let h = self.height as isize;
+        self.remap(
+            self.height,
+            self.width,
+            |x, y| (y, self.height - 1 - x),
+            |a| Coord(h - 1 - a.1, a.0),
+        )
+    }
+
+    /// Rotates 90 degrees counter-clockwise (with `y` pointing south), swapping the dimensions.
+    pub fn rotate_ccw(&self) -> Self {
+        let w = self.width as isize;
+        self.remap(
+            self.height,
+            self.width,
+            |x, y| (self.width - 1 - y, x),
+            |a| Coord(a.1, w - 1 - a.0),
+        )
+    }
+
+    /// Mirrors left to right.
+    pub fn mirror_x(&self) -> Self {
+        let w = self.width as isize;
+        self.remap(
+            self.width,
+            self.height,
+            |x, y| (self.width - 1 - x, y),
+            |a| Coord(w - 1 - a.0, a.1),
+        )
+    }
+
+    /// Mirrors top to bottom.
+    pub fn mirror_y(&self) -> Self {
+        let h = self.height as isize;
+        self.remap(
+            self.width,
+            self.height,
+            |x, y| (x, self.height - 1 - y),
+            |a| Coord(a.0, h - 1 - a.1),
+        )
+    }
+
+    /// The cells to write for `mode`, as offsets from the anchor.
+    fn placed(&self, mode: StampMode) -> impl Iterator<Item = (Coord, BlockID)> + '_ {
+        self.blocks
+            .iter()
+            .enumerate()
+            .filter(move |&(_, &block)| mode == StampMode::Overwrite || block != BlockID::default())
+            .map(move |(i, &block)| {
+                let (x, y) = ((i % self.width) as isize, (i / self.width) as isize);
+                (Coord(x - self.anchor.0, y - self.anchor.1), block)
+            })
+    }
+
+    /// Writes the structure into `chunk` with its anchor at `at`, clipping whatever falls
+    /// outside the chunk.
+    pub fn stamp<const X: usize, const Y: usize>(
+        &self,
+        chunk: &mut ChunkData<X, Y>,
+        at: Coord,
+        mode: StampMode,
+    ) {
+        chunk.extend(
+            self.placed(mode)
+                .map(|(offset, block)| (at + offset, block)),
+        );
+    }
+
+    /// Writes the structure into `world` with its anchor at the world coordinate `at`. Cells
+    /// landing in chunks that aren't loaded are skipped.
+    pub fn stamp_world<const X: usize, const Y: usize>(
+        &self,
+        world: &mut World<X, Y>,
+        at: Coord,
+        mode: StampMode,
+    ) {
+        for (offset, block) in self.placed(mode) {
+            world.set_block(at + offset, block);
+        }
+    }
+}
+
+impl TryFrom<RawStructure> for Structure {
+    type Error = LengthError;
+    fn try_from(raw: RawStructure) -> Result<Self, Self::Error> {
+        Ok(Self::from_blocks(raw.width, raw.height, raw.blocks)?.with_anchor(raw.anchor))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_value::{self, Value};
+
+    // 1 2 3
+    // 4 0 6
+    fn sample() -> Structure {
+        Structure::from_blocks(3, 2, vec![1, 2, 3, 4, 0, 6])
+            .unwrap()
+            .with_anchor(Coord(2, 0))
+    }
+
+    fn rows(structure: &Structure) -> Vec<Vec<BlockID>> {
+        (0..structure.height())
+            .map(|y| {
+                (0..structure.width())
+                    .map(|x| structure.get(x, y).unwrap())
+                    .collect()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn rotations_move_cells_and_anchor() {
+        let cw = sample().rotate_cw();
+        assert_eq!((cw.width(), cw.height()), (2, 3));
+        assert_eq!(rows(&cw), [[4, 1], [0, 2], [6, 3]]);
+        assert_eq!(cw.anchor(), Coord(1, 2));
+        assert_eq!(
+            cw.get(cw.anchor().0 as usize, cw.anchor().1 as usize),
+            Some(3)
+        );
+
+        let ccw = sample().rotate_ccw();
+        assert_eq!(rows(&ccw), [[3, 6], [2, 0], [1, 4]]);
+        assert_eq!(ccw.anchor(), Coord(0, 0));
+        assert_eq!(ccw.rotate_cw(), sample());
+        assert_eq!(sample().rotate_cw().rotate_cw().rotate_cw(), ccw);
+    }
+
+    #[test]
+    fn mirrors_move_cells_and_anchor() {
+        let x = sample().mirror_x();
+        assert_eq!(rows(&x), [[3, 2, 1], [6, 0, 4]]);
+        assert_eq!(x.anchor(), Coord(0, 0));
+        let y = sample().mirror_y();
+        assert_eq!(rows(&y), [[4, 0, 6], [1, 2, 3]]);
+        assert_eq!(y.anchor(), Coord(2, 1));
+        assert_eq!(y.mirror_y(), sample());
+        // An anchor outside the template is mirrored too.
+        let outside = sample().with_anchor(Coord(1, 3)).mirror_y();
+        assert_eq!(outside.anchor(), Coord(1, -2));
+    }
+
+    #[test]
+    fn stamp_in_both_modes() {
+        let mut chunk = ChunkData::<4, 3>::new(9);
+        sample().stamp(&mut chunk, Coord(3, 1), StampMode::SkipAir);
+        assert_eq!(chunk.as_array(), &[[9; 4], [9, 1, 2, 3], [9, 4, 9, 6]]);
+        sample().stamp(&mut chunk, Coord(3, 1), StampMode::Overwrite);
+        assert_eq!(chunk.as_array(), &[[9; 4], [9, 1, 2, 3], [9, 4, 0, 6]]);
+    }
+
+    #[test]
+    fn stamp_clips_to_the_chunk() {
+        let mut chunk = ChunkData::<2, 2>::new(9);
+        sample().stamp(&mut chunk, Coord(1, 1), StampMode::Overwrite);
+        assert_eq!(chunk.as_array(), &[[9, 9], [2, 3]]);
+    }
+
+    #[test]
+    fn stamp_world_across_a_chunk_seam() {
+        let mut world = World::<2, 2>::new();
+        world.insert_chunk(Coord(0, 0), ChunkData::new(9));
+        world.insert_chunk(Coord(1, 0), ChunkData::new(9));
+        // Spans x = 1..=3, crossing from chunk (0, 0) into (1, 0); row y = 2 is unloaded.
+        sample().stamp_world(&mut world, Coord(3, 1), StampMode::SkipAir);
+        assert_eq!(
+            world.get_chunk(Coord(0, 0)).unwrap().as_array(),
+            &[[9, 9], [9, 1]]
+        );
+        assert_eq!(
+            world.get_chunk(Coord(1, 0)).unwrap().as_array(),
+            &[[9, 9], [2, 3]]
+        );
+        assert_eq!(world.get_block(Coord(1, 2)), None);
+
+        world.insert_chunk(Coord(0, 1), ChunkData::new(9));
+        world.insert_chunk(Coord(1, 1), ChunkData::new(9));
+        sample().stamp_world(&mut world, Coord(3, 1), StampMode::SkipAir);
+        assert_eq!(world.get_block(Coord(2, 2)), Some(9));
+        sample().stamp_world(&mut world, Coord(3, 1), StampMode::Overwrite);
+        assert_eq!(world.get_block(Coord(1, 2)), Some(4));
+        assert_eq!(world.get_block(Coord(2, 2)), Some(0));
+        assert_eq!(world.get_block(Coord(3, 2)), Some(6));
+    }
+
+    #[test]
+    fn from_blocks_checks_the_length() {
+        assert!(Structure::from_blocks(2, 3, vec![1; 6]).is_ok());
+        assert_eq!(
+            Structure::from_blocks(2, 3, vec![1; 5]),
+            Err(LengthError {
+                expected: 6,
+                actual: 5
+            })
+        );
+    }
+
+    #[test]
+    #[cfg(target_pointer_width = "64")]
+    fn overflowing_dimensions_are_rejected() {
+        let side = 1 << 32;
+        assert_eq!(
+            Structure::from_blocks(side, side, Vec::new()),
+            Err(LengthError {
+                expected: usize::MAX,
+                actual: 0
+            })
+        );
+        let raw = |width: u64, height: u64| {
+            Value::Map(vec![
+                (Value::Str("width".into()), Value::U64(width)),
+                (Value::Str("height".into()), Value::U64(height)),
+                (
+                    Value::Str("anchor".into()),
+                    Value::Seq(vec![Value::I64(0), Value::I64(0)]),
+                ),
+                (Value::Str("blocks".into()), Value::Seq(Vec::new())),
+            ])
+        };
+        assert!(test_value::from_value::<Structure>(raw(side as u64, 0)).is_ok());
+        assert!(test_value::from_value::<Structure>(raw(side as u64, side as u64)).is_err());
+    }
+
+    #[test]
+    #[should_panic(expected = "overflow")]
+    fn new_panics_on_overflowing_dimensions() {
+        Structure::new(usize::MAX, 2, 0);
+    }
+}
@@ -0,0 +1,146 @@
+use crate::{cell_coord, BlockID, ChunkData, ChunkError, Coord};
+
+/// A chunk that remembers which cells have changed since the last `take_dirty`.
+///
+/// Writes that leave a cell's value unchanged are not recorded. `version` counts the writes
+/// that did change something and never goes back down.
+#[derive(Clone, PartialEq, Debug)]
+pub struct TrackedChunk<const X: usize, const Y: usize, T = BlockID> {
+    chunk: ChunkData<X, Y, T>,
+    dirty: Vec<bool>,
+    version: u64,
+}
+
+impl<const X: usize, const Y: usize, T: Copy + PartialEq> TrackedChunk<X, Y, T> {
+    pub fn new(chunk: ChunkData<X, Y, T>) -> Self {
+        Self {
+            chunk,
+            dirty: vec![false; X * Y],
+            version: 0,
+        }
+    }
+
+    pub fn chunk(&self) -> &ChunkData<X, Y, T> {
+        &self.chunk
+    }
+
+    pub fn into_inner(self) -> ChunkData<X, Y, T> {
+        self.chunk
+    }
+
+    pub fn version(&self) -> u64 {
+        self.version
+    }
+
+    pub fn get(&self, x: usize, y: usize) -> Option<&T> {
+        self.chunk.get(x, y)
+    }
+
+    pub fn set(&mut self, x: usize, y: usize, value: T) -> Result<(), ChunkError> {
+        let cell = self
+            .chunk
+            .get_mut(x, y)
+            .ok_or(ChunkError::OutOfBounds { x, y })?;
+        if *cell != value {
+            *cell = value;
+            self.dirty[y * X + x] = true;
+            self.version += 1;
+        }
+        Ok(())
+    }
+
+    pub fn fill(&mut self, value: T) {
+        let mut changed = false;
+        for ((_, cell), dirty) in self.chunk.iter_mut().zip(&mut self.dirty) {
+            if *cell != value {
+                *cell = value;
+                *dirty = true;
+                changed = true;
+            }
+        }
+        if changed {
+            self.version += 1;
+        }
+    }
+
+    pub fn is_dirty(&self) -> bool {
+        self.dirty.contains(&true)
+    }
+
+    /// Rows containing at least one dirty cell, in ascending order.
+    pub fn dirty_rows(&self) -> impl Iterator<Item = usize> + '_ {
+        self.dirty
+            .chunks(X.max(1))
+            .enumerate()
+            .filter(|(_, row)| row.contains(&true))
+            .map(|(y, _)| y)
+    }
+
+    /// Clears the dirty set, returning the cells it held in row-major order.
+    pub fn take_dirty(&mut self) -> impl Iterator<Item = Coord> {
+        let mut cells = Vec::new();
+        for (i, dirty) in self.dirty.iter_mut().enumerate() {
+            if *dirty {
+                cells.push(cell_coord::<X>(i));
+                *dirty = false;
+            }
+        }
+        cells.into_iter()
+    }
+}
+
+impl<const X: usize, const Y: usize, T: Copy + PartialEq> From<ChunkData<X, Y, T>>
+    for TrackedChunk<X, Y, T>
+{
+    fn from(chunk: ChunkData<X, Y, T>) -> Self {
+        Self::new(chunk)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn set_marks_changed_cells_dirty() {
+        let mut tracked = TrackedChunk::new(ChunkData::<3, 2>::default());
+        assert!(!tracked.is_dirty());
+        assert_eq!(tracked.set(2, 1, 5), Ok(()));
+        assert!(tracked.is_dirty());
+        assert_eq!(tracked.get(2, 1), Some(&5));
+        assert_eq!(tracked.version(), 1);
+        assert_eq!(tracked.dirty_rows().collect::<Vec<_>>(), [1]);
+        assert_eq!(
+            tracked.set(3, 0, 1),
+            Err(ChunkError::OutOfBounds { x: 3, y: 0 })
+        );
+        assert_eq!(tracked.version(), 1);
+    }
+
+    #[test]
+    fn writing_the_same_value_is_not_a_change() {
+        let mut tracked = TrackedChunk::new(ChunkData::<3, 2>::new(4));
+        tracked.set(0, 0, 4).unwrap();
+        tracked.fill(4);
+        assert!(!tracked.is_dirty());
+        assert_eq!(tracked.version(), 0);
+    }
+
+    #[test]
+    fn take_dirty_clears_the_flags() {
+        let mut tracked = TrackedChunk::new(ChunkData::<3, 2>::default());
+        tracked.set(1, 1, 2).unwrap();
+        tracked.set(2, 0, 2).unwrap();
+        assert_eq!(
+            tracked.take_dirty().collect::<Vec<_>>(),
+            [Coord(2, 0), Coord(1, 1)]
+        );
+        assert!(!tracked.is_dirty());
+        assert_eq!(tracked.take_dirty().count(), 0);
+        assert_eq!(tracked.version(), 2);
+
+        tracked.fill(2);
+        assert_eq!(tracked.take_dirty().count(), 4);
+        assert_eq!(tracked.version(), 3);
+    }
+}
@@ -0,0 +1,358 @@
+use std::{
+    convert::TryFrom,
+    fmt,
+    ops::{Deref, DerefMut, Index, IndexMut, Range},
+};
+
+use crate::{Aabb, BlockID, ChunkData, ChunkDataRow, ChunkError, Coord};
+
+/// A chunk allocated directly on the heap, so even very large chunks never pass through the
+/// stack. Derefs to `ChunkData` for everything else.
+pub struct BoxedChunk<const X: usize, const Y: usize, T = BlockID>(Box<ChunkData<X, Y, T>>);
+
+impl<const X: usize, const Y: usize, T: Copy> BoxedChunk<X, Y, T> {
+    fn from_rows(rows: Box<[ChunkDataRow<X, T>]>) -> Self {
+        let rows = match <Box<[ChunkDataRow<X, T>; Y]>>::try_from(rows) {
+            Ok(rows) => rows,
+            Err(_) => unreachable!("rows are always allocated with length Y"),
+        };
+        // There is no safe conversion from a box of the row array to a box of the chunk, so
+        // the pointer is cast.
+        //
+        // SAFETY: `ChunkData<X, Y, T>` is `repr(transparent)` over `[ChunkDataRow<X, T>; Y]`, so
+        // both types have the same size, alignment and bit validity. The allocation behind
+        // `rows` therefore has exactly the layout `Box<ChunkData<X, Y, T>>` requires, and
+        // `into_raw` hands its ownership over to the new box.
+        Self(unsafe { Box::from_raw(Box::into_raw(rows) as *mut ChunkData<X, Y, T>) })
+    }
+
+    pub fn new(value: T) -> Self {
+        Self::from_rows(vec![ChunkDataRow::new(value); Y].into_boxed_slice())
+    }
+
+    pub fn into_inner(self) -> Box<ChunkData<X, Y, T>> {
+        self.0
+    }
+}
+
+impl<const X: usize, const Y: usize, T: Copy + Default> Default for BoxedChunk<X, Y, T> {
+    fn default() -> Self {
+        Self::new(T::default())
+    }
+}
+
+impl<const X: usize, const Y: usize, T: Copy> Clone for BoxedChunk<X, Y, T> {
+    fn clone(&self) -> Self {
+        Self::from_rows(self.0 .0.to_vec().into_boxed_slice())
+    }
+}
+
+impl<const X: usize, const Y: usize, T: fmt::Debug> fmt::Debug for BoxedChunk<X, Y, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl<const X: usize, const Y: usize, T> Deref for BoxedChunk<X, Y, T> {
+    type Target = ChunkData<X, Y, T>;
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl<const X: usize, const Y: usize, T> DerefMut for BoxedChunk<X, Y, T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
+impl<const X: usize, const Y: usize, T> From<Box<ChunkData<X, Y, T>>> for BoxedChunk<X, Y, T> {
+    fn from(chunk: Box<ChunkData<X, Y, T>>) -> Self {
+        Self(chunk)
+    }
+}
+
+/// Where a view's cells sit in the underlying row-major storage.
+#[derive(Clone, Copy, Debug)]
+struct Layout {
+    width: usize,
+    height: usize,
+    stride: usize,
+}
+
+impl Layout {
+    fn len(&self) -> usize {
+        if self.height == 0 {
+            0
+        } else {
+            (self.height - 1) * self.stride + self.width
+        }
+    }
+
+    fn row(&self, y: usize) -> Range<usize> {
+        assert!(y < self.height, "row {} out of bounds", y);
+        y * self.stride..y * self.stride + self.width
+    }
+
+    fn offset(&self, x: usize, y: usize) -> Option<usize> {
+        if x < self.width && y < self.height {
+            Some(y * self.stride + x)
+        } else {
+            None
+        }
+    }
+
+    /// The storage range and layout of `rect`, or `None` unless it lies within the view.
+    fn window(&self, rect: Aabb) -> Option<(Range<usize>, Layout)> {
+        let (x0, y0) = rect.min.to_usize()?;
+        let (x1, y1) = rect.max.to_usize()?;
+        if x0 > x1 || y0 > y1 || x1 >= self.width || y1 >= self.height {
+            return None;
+        }
+        let layout = Layout {
+            width: x1 - x0 + 1,
+            height: y1 - y0 + 1,
+            stride: self.stride,
+        };
+        let start = y0 * self.stride + x0;
+        Some((start..start + layout.len(), layout))
+    }
+}
+
+/// A borrowed rectangle of a chunk's cells, indexed as `view[y][x]` like the chunk itself.
+#[derive(Clone, Copy)]
+pub struct ChunkView<'a, T = BlockID> {
+    cells: &'a [T],
+    layout: Layout,
+}
+
+/// The mutable counterpart of `ChunkView`.
+pub struct ChunkViewMut<'a, T = BlockID> {
+    cells: &'a mut [T],
+    layout: Layout,
+}
+
+impl<'a, T> ChunkView<'a, T> {
+    pub fn width(&self) -> usize {
+        self.layout.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.layout.height
+    }
+
+    pub fn get(&self, x: usize, y: usize) -> Option<&'a T> {
+        self.layout.offset(x, y).map(|i| &self.cells[i])
+    }
+
+    pub fn rows(&self) -> impl Iterator<Item = &'a [T]> + 'a {
+        let (cells, layout) = (self.cells, self.layout);
+        (0..layout.height).map(move |y| &cells[layout.row(y)])
+    }
+
+    /// Cells and their coordinates within the view in row-major order.
+    pub fn iter(&self) -> impl Iterator<Item = (Coord, &'a T)> + 'a {
+        self.rows().enumerate().flat_map(|(y, row)| {
+            row.iter()
+                .enumerate()
+                .map(move |(x, cell)| (Coord(x as isize, y as isize), cell))
+        })
+    }
+
+    /// A sub-view covering `rect`, given in this view's coordinates.
+    pub fn window(&self, rect: Aabb) -> Option<ChunkView<'a, T>> {
+        let (range, layout) = self.layout.window(rect)?;
+        Some(ChunkView {
+            cells: &self.cells[range],
+            layout,
+        })
+    }
+}
+
+impl<'a, T> ChunkViewMut<'a, T> {
+    pub fn width(&self) -> usize {
+        self.layout.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.layout.height
+    }
+
+    pub fn as_view(&self) -> ChunkView<'_, T> {
+        ChunkView {
+            cells: self.cells,
+            layout: self.layout,
+        }
+    }
+
+    pub fn get(&self, x: usize, y: usize) -> Option<&T> {
+        self.layout.offset(x, y).map(|i| &self.cells[i])
+    }
+
+    pub fn get_mut(&mut self, x: usize, y: usize) -> Option<&mut T> {
+        self.layout.offset(x, y).map(move |i| &mut self.cells[i])
+    }
+
+    pub fn set(&mut self, x: usize, y: usize, value: T) -> Result<(), ChunkError> {
+        *self.get_mut(x, y).ok_or(ChunkError::OutOfBounds { x, y })? = value;
+        Ok(())
+    }
+
+    pub fn rows_mut(&mut self) -> impl Iterator<Item = &mut [T]> {
+        let Layout {
+            width,
+            height,
+            stride,
+        } = self.layout;
+        self.cells
+            .chunks_mut(stride.max(1))
+            .take(height)
+            .map(move |row| &mut row[..width])
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = (Coord, &mut T)> {
+        self.rows_mut().enumerate().flat_map(|(y, row)| {
+            row.iter_mut()
+                .enumerate()
+                .map(move |(x, cell)| (Coord(x as isize, y as isize), cell))
+        })
+    }
+
+    pub fn fill(&mut self, value: T)
+    where
+        T: Copy,
+    {
+        for row in self.rows_mut() {
+            row.fill(value);
+        }
+    }
+
+    pub fn window(&self, rect: Aabb) -> Option<ChunkView<'_, T>> {
+        self.as_view().window(rect)
+    }
+
+    pub fn window_mut(&mut self, rect: Aabb) -> Option<ChunkViewMut<'_, T>> {
+        let (range, layout) = self.layout.window(rect)?;
+        Some(ChunkViewMut {
+            cells: &mut self.cells[range],
+            layout,
+        })
+    }
+}
+
+impl<T> Index<usize> for ChunkView<'_, T> {
+    type Output = [T];
+    fn index(&self, y: usize) -> &Self::Output {
+        &self.cells[self.layout.row(y)]
+    }
+}
+
+impl<T> Index<usize> for ChunkViewMut<'_, T> {
+    type Output = [T];
+    fn index(&self, y: usize) -> &Self::Output {
+        &self.cells[self.layout.row(y)]
+    }
+}
+
+impl<T> IndexMut<usize> for ChunkViewMut<'_, T> {
+    fn index_mut(&mut self, y: usize) -> &mut Self::Output {
+        &mut self.cells[self.layout.row(y)]
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for ChunkView<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.rows()).finish()
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for ChunkViewMut<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.as_view().fmt(f)
+    }
+}
+
+impl<const X: usize, const Y: usize, T: Copy> ChunkData<X, Y, T> {
+    pub fn view(&self) -> ChunkView<'_, T> {
+        ChunkView {
+            cells: self.as_array().as_flattened(),
+            layout: Layout {
+                width: X,
+                height: Y,
+                stride: X,
+            },
+        }
+    }
+
+    pub fn view_mut(&mut self) -> ChunkViewMut<'_, T> {
+        ChunkViewMut {
+            cells: self.as_array_mut().as_flattened_mut(),
+            layout: Layout {
+                width: X,
+                height: Y,
+                stride: X,
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn boxed_chunks_read_back_through_views() {
+        let mut boxed = BoxedChunk::<3, 2>::new(7);
+        boxed[1][2] = 4;
+        let view = boxed.view();
+        assert_eq!((view.width(), view.height()), (3, 2));
+        assert_eq!(
+            view.rows().collect::<Vec<_>>(),
+            [&[7, 7, 7][..], &[7, 7, 4][..]]
+        );
+        assert_eq!(view.get(2, 1), Some(&4));
+        assert_eq!(view.get(3, 0), None);
+
+        let copy = boxed.clone();
+        boxed.view_mut().fill(1);
+        assert_eq!(copy.view()[1], [7, 7, 4]);
+        assert_eq!(*boxed.into_inner(), ChunkData::new(1));
+        assert_eq!(*BoxedChunk::<2, 5>::default(), ChunkData::default());
+    }
+
+    #[test]
+    fn boxed_zero_sized_chunks() {
+        let boxed = BoxedChunk::<0, 3>::new(1);
+        assert_eq!(boxed.view().height(), 3);
+        assert!(boxed.view().iter().next().is_none());
+        assert_eq!(BoxedChunk::<4, 0>::new(1).view().rows().count(), 0);
+    }
+
+    #[test]
+    fn windows_share_the_chunk_layout() {
+        let mut chunk = ChunkData::<4, 3>::default();
+        for (c, cell) in chunk.iter_mut() {
+            *cell = (10 * c.1 + c.0) as BlockID;
+        }
+        let window = chunk
+            .view()
+            .window(Aabb::new(Coord(1, 1), Coord(2, 2)))
+            .unwrap();
+        assert_eq!(format!("{:?}", window), "[[11, 12], [21, 22]]");
+        assert!(chunk
+            .view()
+            .window(Aabb::new(Coord(3, 0), Coord(4, 0)))
+            .is_none());
+
+        let mut view = chunk.view_mut();
+        let mut window = view
+            .window_mut(Aabb::new(Coord(2, 0), Coord(3, 1)))
+            .unwrap();
+        assert_eq!(window.set(1, 1, 99), Ok(()));
+        assert_eq!(
+            window.set(2, 0, 99),
+            Err(ChunkError::OutOfBounds { x: 2, y: 0 })
+        );
+        assert_eq!(chunk[1][3], 99);
+    }
+}
@@ -0,0 +1,201 @@
+use std::{convert::TryFrom, error, fmt};
+
+use crate::{rle::read_u32, BlockID, ChunkData, Coord, DecodeError};
+
+const VERSION: u8 = 1;
+
+/// The number of bytes each block id takes in `ChunkData::to_bytes`.
+#[derive(PartialEq, Eq, Hash, Clone, Copy, Debug)]
+pub enum IdWidth {
+    U16,
+    U32,
+}
+
+impl IdWidth {
+    pub fn bytes(&self) -> usize {
+        match self {
+            IdWidth::U16 => 2,
+            IdWidth::U32 => 4,
+        }
+    }
+
+    fn from_bytes(bytes: u8) -> Option<Self> {
+        match bytes {
+            2 => Some(IdWidth::U16),
+            4 => Some(IdWidth::U32),
+            _ => None,
+        }
+    }
+}
+
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub struct IdRangeError {
+    pub block: BlockID,
+    pub at: Coord,
+    pub width: IdWidth,
+}
+
+impl fmt::Display for IdRangeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "block id {} at {} does not fit in {} bytes",
+            self.block,
+            self.at,
+            self.width.bytes()
+        )
+    }
+}
+
+impl error::Error for IdRangeError {}
+
+impl<const X: usize, const Y: usize> ChunkData<X, Y> {
+    /// Encodes every cell as a fixed-width little-endian id, so the bytes are the same on every
+    /// platform regardless of the size of `BlockID`.
+    ///
+    /// Layout, version 1:
+    ///
+    /// | bytes             | field                                 |
+    /// |-------------------|---------------------------------------|
+    /// | 1                 | format version, currently `1`         |
+    /// | 1                 | id width in bytes, `2` or `4`         |
+    /// | 4                 | chunk width `X`, little-endian `u32`  |
+    /// | 4                 | chunk height `Y`, little-endian `u32` |
+    /// | `X * Y * width`   | block ids in row-major order          |
+    pub fn to_bytes(&self, width: IdWidth) -> Result<Vec<u8>, IdRangeError> {
+        let mut out = Vec::with_capacity(10 + X * Y * width.bytes());
+        out.push(VERSION);
+        out.push(width.bytes() as u8);
+        out.extend_from_slice(&(X as u32).to_le_bytes());
+        out.extend_from_slice(&(Y as u32).to_le_bytes());
+        for (at, &block) in self.iter() {
+            let error = IdRangeError { block, at, width };
+            match width {
+                IdWidth::U16 => {
+                    out.extend_from_slice(&u16::try_from(block).map_err(|_| error)?.to_le_bytes())
+                }
+                IdWidth::U32 => {
+                    out.extend_from_slice(&u32::try_from(block).map_err(|_| error)?.to_le_bytes())
+                }
+            }
+        }
+        Ok(out)
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, DecodeError> {
+        let mut input = bytes;
+        let (&version, rest) = input.split_first().ok_or(DecodeError::Truncated)?;
+        if version != VERSION {
+            return Err(DecodeError::UnsupportedVersion(version));
+        }
+        let (&width, rest) = rest.split_first().ok_or(DecodeError::Truncated)?;
+        input = rest;
+        let id_width = IdWidth::from_bytes(width).ok_or(DecodeError::UnsupportedIdWidth(width))?;
+        let (x, y) = (read_u32(&mut input)?, read_u32(&mut input)?);
+        if x as usize != X || y as usize != Y {
+            return Err(DecodeError::WrongDimensions {
+                width: x,
+                height: y,
+            });
+        }
+        let len = X * Y * id_width.bytes();
+        if input.len() < len {
+            return Err(DecodeError::Truncated);
+        }
+        if input.len() > len {
+            return Err(DecodeError::TrailingBytes);
+        }
+        let mut chunk = Self::default();
+        let cells = input.chunks_exact(id_width.bytes());
+        for ((_, cell), raw) in chunk.iter_mut().zip(cells) {
+            let id = match id_width {
+                IdWidth::U16 => u16::from_le_bytes([raw[0], raw[1]]) as u32,
+                IdWidth::U32 => u32::from_le_bytes([raw[0], raw[1], raw[2], raw[3]]),
+            };
+            *cell = BlockID::try_from(id).map_err(|_| DecodeError::BlockIdOutOfRange(id.into()))?;
+        }
+        Ok(chunk)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn numbered() -> ChunkData<3, 2> {
+        let mut chunk = ChunkData::default();
+        for (i, (_, cell)) in chunk.iter_mut().enumerate() {
+            *cell = 1000 * i;
+        }
+        chunk
+    }
+
+    #[test]
+    fn u16_round_trip() {
+        let bytes = numbered().to_bytes(IdWidth::U16).unwrap();
+        assert_eq!(bytes.len(), 10 + 6 * 2);
+        assert_eq!(bytes[..2], [VERSION, 2]);
+        assert_eq!(bytes[12..14], 1000u16.to_le_bytes());
+        assert_eq!(ChunkData::<3, 2>::from_bytes(&bytes), Ok(numbered()));
+    }
+
+    #[test]
+    fn u32_round_trip() {
+        let mut chunk = numbered();
+        chunk[1][2] = u32::MAX as BlockID;
+        let bytes = chunk.to_bytes(IdWidth::U32).unwrap();
+        assert_eq!(bytes.len(), 10 + 6 * 4);
+        assert_eq!(bytes[1], 4);
+        assert_eq!(ChunkData::<3, 2>::from_bytes(&bytes), Ok(chunk));
+    }
+
+    #[test]
+    fn to_bytes_rejects_ids_too_wide_for_u16() {
+        let mut chunk = numbered();
+        chunk[1][0] = 70_000;
+        assert_eq!(
+            chunk.to_bytes(IdWidth::U16),
+            Err(IdRangeError {
+                block: 70_000,
+                at: Coord(0, 1),
+                width: IdWidth::U16
+            })
+        );
+        assert!(chunk.to_bytes(IdWidth::U32).is_ok());
+    }
+
+    #[test]
+    fn from_bytes_rejects_truncated_input() {
+        let bytes = numbered().to_bytes(IdWidth::U16).unwrap();
+        for len in [0, 1, 2, 9, 10, bytes.len() - 1] {
+            assert_eq!(
+                ChunkData::<3, 2>::from_bytes(&bytes[..len]),
+                Err(DecodeError::Truncated),
+                "length {}",
+                len
+            );
+        }
+    }
+
+    #[test]
+    fn from_bytes_rejects_bad_headers_and_trailing_bytes() {
+        let mut bytes = numbered().to_bytes(IdWidth::U16).unwrap();
+        bytes.push(0);
+        assert_eq!(
+            ChunkData::<3, 2>::from_bytes(&bytes),
+            Err(DecodeError::TrailingBytes)
+        );
+        bytes[1] = 3;
+        assert_eq!(
+            ChunkData::<3, 2>::from_bytes(&bytes),
+            Err(DecodeError::UnsupportedIdWidth(3))
+        );
+        assert_eq!(
+            ChunkData::<2, 3>::from_bytes(&numbered().to_bytes(IdWidth::U16).unwrap()),
+            Err(DecodeError::WrongDimensions {
+                width: 3,
+                height: 2
+            })
+        );
+    }
+}